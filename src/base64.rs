@@ -0,0 +1,140 @@
+//! Base64 with the standard RFC 4648 alphabet and `=` padding.
+
+use thiserror::Error;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DecodeError {
+    #[error("invalid base64 character {0:?} at position {1}")]
+    InvalidCharacter(char, usize),
+    #[error("base64 input length isn't a multiple of four")]
+    InvalidLength,
+    #[error("invalid base64 padding")]
+    InvalidPadding,
+}
+
+/// Encodes `input`, padding the output to a multiple of four characters with `=`.
+pub fn encode(input: &[u8]) -> String {
+    let mut output = String::with_capacity(input.len() / 3 * 4 + 4);
+
+    for group in input.chunks(3) {
+        let bits = group.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | u32::from(byte) << (16 - 8 * i)
+        });
+
+        for i in 0..4 {
+            if i <= group.len() {
+                let sextet = (bits >> (18 - 6 * i)) & 0x3f;
+                output.push(char::from(ALPHABET[sextet as usize]));
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+/// Decodes padded base64. Whitespace and unpadded input are rejected.
+pub fn decode(input: &str) -> Result<Vec<u8>, DecodeError> {
+    // Every group of four characters is complete, padded out with `=` if need be.
+    let incomplete = input.len() % 4;
+    if incomplete != 0 {
+        return Err(DecodeError::InvalidLength);
+    }
+
+    let padding = input.bytes().rev().take_while(|&b| b == b'=').count();
+    if padding > 2 {
+        return Err(DecodeError::InvalidPadding);
+    }
+    let data = &input[..input.len() - padding];
+
+    let mut output = Vec::with_capacity(input.len() / 4 * 3);
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    for (i, c) in data.char_indices() {
+        let value = sextet(c).ok_or(DecodeError::InvalidCharacter(c, i))?;
+        bits = bits << 6 | u32::from(value);
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            output.push((bits >> bit_count) as u8);
+        }
+    }
+
+    // The bits left over after the last whole byte must be zero, or the input
+    // wasn't produced by an encoder.
+    if bits & ((1 << bit_count) - 1) != 0 {
+        return Err(DecodeError::InvalidPadding);
+    }
+    Ok(output)
+}
+
+fn sextet(c: char) -> Option<u8> {
+    let value = match c {
+        'A'..='Z' => c as u8 - b'A',
+        'a'..='z' => c as u8 - b'a' + 26,
+        '0'..='9' => c as u8 - b'0' + 52,
+        '+' => 62,
+        '/' => 63,
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The test vectors from RFC 4648, section 10.
+    const VECTORS: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[test]
+    fn test_encode() {
+        for (plain, encoded) in VECTORS {
+            assert_eq!(encode(plain.as_bytes()), encoded);
+        }
+        assert_eq!(encode(&[0xfb, 0xff, 0xbf]), "+/+/");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (plain, encoded) in VECTORS {
+            assert_eq!(decode(encoded).unwrap(), plain.as_bytes());
+        }
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 0..bytes.len() {
+            assert_eq!(decode(&encode(&bytes[..len])).unwrap(), &bytes[..len]);
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("").unwrap(), b"");
+        assert_eq!(decode("Zm9vYmFy").unwrap(), b"foobar");
+        assert_eq!(
+            decode("YWxhZGRpbjpvcGVuc2VzYW1l").unwrap(),
+            b"aladdin:opensesame"
+        );
+        assert_eq!(decode("Zg==").unwrap(), b"f");
+        assert_eq!(decode("Zm8=").unwrap(), b"fo");
+
+        assert_eq!(decode("Zm9"), Err(DecodeError::InvalidLength));
+        assert_eq!(
+            decode("Zm9v!mFy"),
+            Err(DecodeError::InvalidCharacter('!', 4))
+        );
+        assert_eq!(decode("Z==="), Err(DecodeError::InvalidPadding));
+        assert_eq!(decode("Zh=="), Err(DecodeError::InvalidPadding));
+        assert_eq!(decode("Zg=a"), Err(DecodeError::InvalidCharacter('=', 2)));
+    }
+}
@@ -0,0 +1,420 @@
+//! Dependency-free gzip and zlib compression: LZ77 matching over a 32 KiB window,
+//! encoded with the fixed Huffman codes from RFC 1951.
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_SIZE: usize = 1 << 15;
+/// How many earlier positions with the same hash are tried before giving up.
+const MAX_CHAIN: usize = 64;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// A content coding the server can compress response bodies with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    /// The name used for the coding in `Accept-Encoding` and `Content-Encoding`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Encoding::Gzip => gzip(data),
+            Encoding::Deflate => zlib(data),
+        }
+    }
+}
+
+/// Picks the encoding an `Accept-Encoding` header value prefers, by its `q` values,
+/// with gzip winning ties. A coding that isn't listed gets the weight of `*`, if
+/// that is, and `None` means neither is acceptable.
+pub fn select_encoding(accept_encoding: &str) -> Option<Encoding> {
+    let mut codings = Vec::new();
+    for coding in accept_encoding.split(',') {
+        let mut params = coding.split(';').map(str::trim);
+        let name = params.next().unwrap_or("");
+        let q = match params.find_map(|param| param.strip_prefix("q=")) {
+            Some(q) => match q.parse::<f32>() {
+                Ok(q) => q,
+                Err(_) => continue,
+            },
+            None => 1.0,
+        };
+        codings.push((name, q));
+    }
+
+    let weight = |encoding: Encoding| {
+        let listed = |name: &str| {
+            codings
+                .iter()
+                .find(|(coding, _)| coding.eq_ignore_ascii_case(name))
+                .map(|&(_, q)| q)
+        };
+        listed(encoding.as_str())
+            .or_else(|| listed("*"))
+            .unwrap_or(0.0)
+    };
+
+    let (gzip, deflate) = (weight(Encoding::Gzip), weight(Encoding::Deflate));
+    if gzip <= 0.0 && deflate <= 0.0 {
+        None
+    } else if gzip >= deflate {
+        Some(Encoding::Gzip)
+    } else {
+        Some(Encoding::Deflate)
+    }
+}
+
+/// Compresses `data` into a gzip member (RFC 1952).
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // Magic, deflate method, no flags, no mtime, no extra flags, unknown OS.
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Compresses `data` into a zlib stream (RFC 1950), which is what the `deflate`
+/// content coding actually means.
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32 KiB window, no preset dictionary, fastest-compression hint;
+    // the check bits make the two header bytes a multiple of 31.
+    let mut out = vec![0x78, 0x01];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Compresses `data` into a single raw deflate block (RFC 1951).
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // Final block, fixed Huffman codes.
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+
+    let mut head = vec![usize::MAX; HASH_SIZE];
+    let mut prev = vec![usize::MAX; data.len()];
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let (length, distance) = longest_match(data, pos, &head, &prev);
+
+        if length >= MIN_MATCH {
+            write_length(&mut writer, length);
+            write_distance(&mut writer, distance);
+        } else {
+            write_literal(&mut writer, data[pos] as u16);
+        }
+
+        for i in pos..pos + length.max(1) {
+            if i + MIN_MATCH <= data.len() {
+                let hash = hash(&data[i..]);
+                prev[i] = head[hash];
+                head[hash] = i;
+            }
+        }
+        pos += length.max(1);
+    }
+
+    write_literal(&mut writer, 256);
+    writer.finish()
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+    value.wrapping_mul(2654435761) >> 7 & (HASH_SIZE - 1)
+}
+
+fn longest_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> (usize, usize) {
+    if pos + MIN_MATCH > data.len() {
+        return (0, 0);
+    }
+
+    let max_length = MAX_MATCH.min(data.len() - pos);
+    let (mut best_length, mut best_distance) = (0, 0);
+
+    let mut candidate = head[hash(&data[pos..])];
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || pos - candidate > WINDOW_SIZE {
+            break;
+        }
+
+        let length = data[candidate..]
+            .iter()
+            .zip(&data[pos..pos + max_length])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if length > best_length {
+            best_length = length;
+            best_distance = pos - candidate;
+            if length == max_length {
+                break;
+            }
+        }
+
+        candidate = prev[candidate];
+    }
+
+    (best_length, best_distance)
+}
+
+fn write_literal(writer: &mut BitWriter, symbol: u16) {
+    let (code, bits) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xc0 + symbol - 280, 8),
+    };
+    writer.write_code(code, bits);
+}
+
+fn write_length(writer: &mut BitWriter, length: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= length)
+        .expect("match length is at least 3");
+
+    write_literal(writer, 257 + index as u16);
+    writer.write_bits(
+        (length - LENGTH_BASE[index] as usize) as u32,
+        LENGTH_EXTRA[index],
+    );
+}
+
+fn write_distance(writer: &mut BitWriter, distance: usize) {
+    let index = DISTANCE_BASE
+        .iter()
+        .rposition(|&base| base as usize <= distance)
+        .expect("match distance is at least 1");
+
+    writer.write_code(index as u16, 5);
+    writer.write_bits(
+        (distance - DISTANCE_BASE[index] as usize) as u32,
+        DISTANCE_EXTRA[index],
+    );
+}
+
+/// Packs bits least-significant first, as deflate streams are laid out.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    len: u8,
+}
+
+impl BitWriter {
+    fn write_bits(&mut self, value: u32, bits: u8) {
+        for i in 0..bits {
+            self.buffer |= (value >> i & 1) << self.len;
+            self.len += 1;
+            if self.len == 8 {
+                self.out.push(self.buffer as u8);
+                self.buffer = 0;
+                self.len = 0;
+            }
+        }
+    }
+
+    /// Huffman codes are written starting from their most significant bit.
+    fn write_code(&mut self, code: u16, bits: u8) {
+        for i in (0..bits).rev() {
+            self.write_bits((code >> i & 1) as u32, 1);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+/// CRC-32 (IEEE 802.3), as stored in the gzip trailer.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = crc >> 1 ^ 0xedb8_8320 & mask;
+        }
+    }
+    !crc
+}
+
+/// Adler-32, as stored in the zlib trailer.
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % MOD;
+        b = (b + a) % MOD;
+    }
+    b << 16 | a
+}
+
+/// A minimal gzip decoder understanding the fixed-Huffman blocks produced by [`gzip`],
+/// used to check that compressed output round-trips.
+#[cfg(test)]
+pub(crate) fn gunzip(data: &[u8]) -> Vec<u8> {
+    assert_eq!(&data[..3], [0x1f, 0x8b, 8], "not a gzip member");
+
+    let inflated = inflate(&data[10..data.len() - 8]);
+    let trailer = &data[data.len() - 8..];
+    assert_eq!(crc32(&inflated).to_le_bytes(), trailer[..4]);
+    assert_eq!((inflated.len() as u32).to_le_bytes(), trailer[4..]);
+    inflated
+}
+
+/// The zlib counterpart of [`gunzip`].
+#[cfg(test)]
+pub(crate) fn unzlib(data: &[u8]) -> Vec<u8> {
+    assert_eq!(
+        (u16::from(data[0]) << 8 | u16::from(data[1])) % 31,
+        0,
+        "bad zlib header"
+    );
+
+    let inflated = inflate(&data[2..data.len() - 4]);
+    assert_eq!(adler32(&inflated).to_be_bytes(), data[data.len() - 4..]);
+    inflated
+}
+
+#[cfg(test)]
+pub(crate) fn inflate(data: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let mut bit = |count: u8| -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            value |= ((data[pos / 8] >> (pos % 8)) as u32 & 1) << i;
+            pos += 1;
+        }
+        value
+    };
+
+    assert_eq!(bit(1), 1, "expected a single final block");
+    assert_eq!(bit(2), 1, "expected fixed Huffman codes");
+
+    let mut out: Vec<u8> = Vec::new();
+    loop {
+        let mut code = 0;
+        for _ in 0..7 {
+            code = code << 1 | bit(1);
+        }
+        let symbol = if code <= 0x17 {
+            256 + code
+        } else {
+            code = code << 1 | bit(1);
+            match code {
+                0x30..=0xbf => code - 0x30,
+                0xc0..=0xc7 => 280 + code - 0xc0,
+                _ => 144 + (code << 1 | bit(1)) - 0x190,
+            }
+        };
+
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return out,
+            _ => {
+                let index = (symbol - 257) as usize;
+                let length = LENGTH_BASE[index] as usize + bit(LENGTH_EXTRA[index]) as usize;
+
+                let mut index = 0;
+                for _ in 0..5 {
+                    index = index << 1 | bit(1) as usize;
+                }
+                let distance = DISTANCE_BASE[index] as usize + bit(DISTANCE_EXTRA[index]) as usize;
+
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_gzip_round_trip() {
+        let inputs: [&[u8]; 5] = [
+            b"",
+            b"a",
+            b"hello hello hello hello hello",
+            &[0, 1, 2, 255, 254, 253, 0, 1, 2, 255, 254, 253],
+            &b"abcdefghij".repeat(5000),
+        ];
+
+        for input in inputs {
+            assert_eq!(gunzip(&gzip(input)), input);
+            assert_eq!(unzlib(&zlib(input)), input);
+        }
+    }
+
+    #[test]
+    fn test_gzip_shrinks_repetitive_input() {
+        let input = b"the quick brown fox ".repeat(100);
+        assert!(gzip(&input).len() < input.len() / 4);
+    }
+
+    #[test]
+    fn test_select_encoding() {
+        assert_eq!(select_encoding("gzip"), Some(Encoding::Gzip));
+        assert_eq!(select_encoding("deflate"), Some(Encoding::Deflate));
+        assert_eq!(
+            select_encoding("gzip;q=0.5, deflate;q=0.8"),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(
+            select_encoding("deflate;q=0.5, GZIP;q=0.8"),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(select_encoding("deflate, gzip"), Some(Encoding::Gzip));
+        assert_eq!(select_encoding("*"), Some(Encoding::Gzip));
+        assert_eq!(select_encoding("gzip;q=0, *"), Some(Encoding::Deflate));
+        assert_eq!(select_encoding("deflate, br"), Some(Encoding::Deflate));
+        assert_eq!(select_encoding("gzip;q=0"), None);
+        assert_eq!(select_encoding("br"), None);
+        assert_eq!(select_encoding(""), None);
+    }
+}
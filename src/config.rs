@@ -0,0 +1,705 @@
+use crate::cors::Cors;
+use crate::error_pages::ErrorPages;
+use crate::files::content_type_for_extension;
+use crate::http::{parse_media_type, HttpContent, HttpContentType, HttpStatusCode, SERVER};
+use crate::router::TrailingSlash;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ConfigError {
+    #[error("{0} needs a value")]
+    MissingValue(String),
+    #[error("unknown argument: {0}")]
+    UnknownArgument(String),
+    #[error("invalid host {0:?}: expected a host name or IP address")]
+    InvalidHost(String),
+    #[error("invalid port {0:?}: expected a number between 0 and 65535")]
+    InvalidPort(String),
+    #[error("invalid thread count {0:?}: expected a positive number")]
+    InvalidThreads(String),
+    #[error("invalid timeout {0:?}: expected a positive number of seconds")]
+    InvalidTimeout(String),
+    #[error("invalid interval {0:?}: expected a number of seconds")]
+    InvalidInterval(String),
+    #[error("invalid size {0:?}: expected a number of bytes")]
+    InvalidSize(String),
+    #[error("invalid directory {0:?}: {1}")]
+    InvalidDirectory(PathBuf, String),
+    #[error("invalid error page {0:?}: {1}")]
+    InvalidErrorPage(String, String),
+    #[error("invalid rate limit {0:?}: expected a positive number of requests a second")]
+    InvalidRateLimit(String),
+    #[error("invalid log format {0:?}: expected common or json")]
+    InvalidLogFormat(String),
+    #[error("invalid trailing slash policy {0:?}: expected strict, redirect or ignore")]
+    InvalidTrailingSlash(String),
+}
+
+/// The format of the access log.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    /// Close to the Common Log Format, for reading.
+    Common,
+    /// One JSON object per line, for log aggregators.
+    Json,
+}
+
+/// Server settings, parsed once at startup from the command line and environment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// The host name or IP address to listen on. IPv6 addresses are kept without
+    /// brackets, and `::` listens on every interface.
+    pub host: String,
+    pub port: u16,
+    /// Directory served under `/files`.
+    pub directory: Option<PathBuf>,
+    /// Document root whose files are served from `/`, for any path no other route
+    /// claims.
+    pub root: Option<PathBuf>,
+    /// Whether directories without an `index.html` are answered with a listing.
+    pub listing: bool,
+    /// Sent as the `Cache-Control` header with files from the directory and document
+    /// root, such as `max-age=3600`.
+    pub cache_control: Option<String>,
+    /// The content type of served files whose extension isn't recognised.
+    pub default_content_type: HttpContentType,
+    /// Number of worker threads handling connections.
+    pub threads: usize,
+    /// How long a read or write of a request in progress may block before the
+    /// connection is given up on.
+    pub timeout: Duration,
+    /// How long a client has to send the whole request line and headers, from their
+    /// first byte, before it's answered with `408 Request Timeout`.
+    pub header_timeout: Duration,
+    /// Requests with a longer request line are answered with
+    /// `431 Request Header Fields Too Large`.
+    pub max_request_line: usize,
+    /// Requests whose request line and headers together are larger are answered with
+    /// `431 Request Header Fields Too Large`.
+    pub max_header_size: usize,
+    /// Requests with a larger body are answered with `413 Payload Too Large`.
+    pub max_body_size: usize,
+    /// Whether a request with a header line that isn't `name: value` is answered with
+    /// `400 Bad Request`, rather than having the line ignored.
+    pub strict_headers: bool,
+    /// What to do with a request whose path only matches a route with its trailing
+    /// slash added or taken off.
+    pub trailing_slash: TrailingSlash,
+    /// Whether each handled request is logged to stderr.
+    pub log: bool,
+    /// How each access log line is written.
+    pub log_format: LogFormat,
+    /// How often a summary of the connection and request counts is logged, or zero
+    /// not to.
+    pub stats_interval: Duration,
+    /// Whether `GET /healthz` answers `ok`, for load balancer liveness checks.
+    pub health_check: bool,
+    /// Puts the server in maintenance mode, answering every request but health checks
+    /// with `503 Service Unavailable` and this as its `Retry-After`.
+    pub maintenance: Option<Duration>,
+    /// The `Server` header sent with every response, or `None` to leave it out.
+    pub server: Option<String>,
+    /// How many requests a second each client IP address may make, in bursts of up
+    /// to as many, before being answered with `429 Too Many Requests`. `None` for no
+    /// limit.
+    pub rate_limit: Option<u32>,
+    /// Cross-origin requests are allowed when set.
+    pub cors: Option<Cors>,
+    /// Bodies for error responses sent without one.
+    pub error_pages: ErrorPages,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            host: String::from("127.0.0.1"),
+            port: 4221,
+            directory: None,
+            root: None,
+            listing: true,
+            cache_control: None,
+            default_content_type: HttpContentType::ApplicationOctetStream,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            timeout: Duration::from_secs(30),
+            header_timeout: Duration::from_secs(20),
+            max_request_line: 8 * 1024,
+            max_header_size: 64 * 1024,
+            max_body_size: 10 * 1024 * 1024,
+            strict_headers: false,
+            trailing_slash: TrailingSlash::Strict,
+            log: true,
+            log_format: LogFormat::Common,
+            stats_interval: Duration::ZERO,
+            health_check: true,
+            maintenance: None,
+            server: Some(SERVER.to_string()),
+            rate_limit: None,
+            cors: None,
+            error_pages: ErrorPages::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config from the process arguments and environment, and checks it
+    /// with [`Config::validate`].
+    pub fn from_env() -> Result<Config, ConfigError> {
+        let config = Config::parse(std::env::args().skip(1), |name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Builds a config from command-line `args` (without the program name) and the
+    /// environment variables returned by `env`. Flags take precedence over `HOST`,
+    /// `PORT` and `THREADS`, and `LOG=off` or `--quiet` turns request logging off.
+    pub fn parse<I, E>(args: I, env: E) -> Result<Config, ConfigError>
+    where
+        I: IntoIterator<Item = String>,
+        E: Fn(&str) -> Option<String>,
+    {
+        let mut config = Config::default();
+
+        if let Some(host) = env("HOST") {
+            config.set_host(host)?;
+        }
+        if let Some(port) = env("PORT") {
+            config.port = parse_port(port)?;
+        }
+        if let Some(threads) = env("THREADS") {
+            config.threads = parse_threads(threads)?;
+        }
+        if env("LOG").is_some_and(|log| log.eq_ignore_ascii_case("off")) {
+            config.log = false;
+        }
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| ConfigError::MissingValue(arg.clone()))
+            };
+
+            match arg.as_str() {
+                "--directory" => config.directory = Some(PathBuf::from(value()?)),
+                "--root" => config.root = Some(PathBuf::from(value()?)),
+                "--host" => config.set_host(value()?)?,
+                "--port" => config.port = parse_port(value()?)?,
+                "--threads" => config.threads = parse_threads(value()?)?,
+                "--timeout" => config.timeout = parse_timeout(value()?)?,
+                "--header-timeout" => config.header_timeout = parse_timeout(value()?)?,
+                "--stats-interval" => {
+                    let interval = value()?;
+                    let secs = interval
+                        .parse()
+                        .map_err(|_| ConfigError::InvalidInterval(interval))?;
+                    config.stats_interval = Duration::from_secs(secs);
+                }
+                "--max-request-line" => config.max_request_line = parse_size(value()?)?,
+                "--max-header-size" => config.max_header_size = parse_size(value()?)?,
+                "--max-body-size" => config.max_body_size = parse_size(value()?)?,
+                "--no-listing" => config.listing = false,
+                "--strict-headers" => config.strict_headers = true,
+                "--trailing-slash" => {
+                    let policy = value()?;
+                    config.trailing_slash = policy
+                        .parse()
+                        .map_err(|_| ConfigError::InvalidTrailingSlash(policy))?;
+                }
+                "--cache-control" => config.cache_control = Some(value()?),
+                "--default-content-type" => {
+                    config.default_content_type = parse_media_type(&value()?).0;
+                }
+                "--quiet" => config.log = false,
+                "--log-format" => {
+                    let format = value()?;
+                    config.log_format = match format.as_str() {
+                        "common" => LogFormat::Common,
+                        "json" => LogFormat::Json,
+                        _ => return Err(ConfigError::InvalidLogFormat(format)),
+                    };
+                }
+                "--no-health-check" => config.health_check = false,
+                "--maintenance" => {
+                    let retry_after = value()?;
+                    let secs = retry_after
+                        .parse()
+                        .map_err(|_| ConfigError::InvalidInterval(retry_after))?;
+                    config.maintenance = Some(Duration::from_secs(secs));
+                }
+                "--rate-limit" => {
+                    let rate = value()?;
+                    match rate.parse() {
+                        Ok(rate) if rate > 0 => config.rate_limit = Some(rate),
+                        _ => return Err(ConfigError::InvalidRateLimit(rate)),
+                    }
+                }
+                "--server" => config.server = Some(value()?),
+                "--no-server" => config.server = None,
+                "--cors" => {
+                    config.cors.get_or_insert_with(Cors::default);
+                }
+                "--cors-origin" => {
+                    let origin = value()?;
+                    config
+                        .cors
+                        .get_or_insert_with(|| Cors {
+                            allowed_origins: Vec::new(),
+                        })
+                        .allowed_origins
+                        .push(origin);
+                }
+                "--error-page" => {
+                    let (status, page) = parse_error_page(value()?)?;
+                    config.error_pages.insert(status, page);
+                }
+                "--no-error-pages" => config.error_pages = ErrorPages::empty(),
+                _ => return Err(ConfigError::UnknownArgument(arg)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Checks the settings that depend on the filesystem: the directory and document
+    /// root, if given, must exist and be directories.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for directory in [&self.directory, &self.root].into_iter().flatten() {
+            let invalid = |reason: String| ConfigError::InvalidDirectory(directory.clone(), reason);
+            let metadata = std::fs::metadata(directory).map_err(|e| invalid(e.to_string()))?;
+            if !metadata.is_dir() {
+                return Err(invalid(String::from("not a directory")));
+            }
+        }
+        Ok(())
+    }
+
+    /// The `host:port` address to listen on, with an IPv6 host in brackets.
+    pub fn bind_addr(&self) -> String {
+        match self.host.parse::<Ipv6Addr>() {
+            Ok(_) => format!("[{}]:{}", self.host, self.port),
+            Err(_) => format!("{}:{}", self.host, self.port),
+        }
+    }
+
+    /// Sets the host from a `HOST` or `--host` value: a host name, an IP address, an
+    /// IPv6 address in brackets, or an IP address with a port, which sets that too.
+    fn set_host(&mut self, host: String) -> Result<(), ConfigError> {
+        if let Ok(addr) = host.parse::<SocketAddr>() {
+            self.host = addr.ip().to_string();
+            self.port = addr.port();
+        } else if let Some(bracketed) = host.strip_prefix('[') {
+            let ip = bracketed
+                .strip_suffix(']')
+                .and_then(|ip| ip.parse::<Ipv6Addr>().ok())
+                .ok_or(ConfigError::InvalidHost(host))?;
+            self.host = ip.to_string();
+        } else {
+            self.host = host;
+        }
+        Ok(())
+    }
+}
+
+fn parse_port(port: String) -> Result<u16, ConfigError> {
+    port.parse().map_err(|_| ConfigError::InvalidPort(port))
+}
+
+fn parse_size(size: String) -> Result<usize, ConfigError> {
+    size.parse().map_err(|_| ConfigError::InvalidSize(size))
+}
+
+fn parse_threads(threads: String) -> Result<usize, ConfigError> {
+    match threads.parse() {
+        Ok(threads) if threads > 0 => Ok(threads),
+        _ => Err(ConfigError::InvalidThreads(threads)),
+    }
+}
+
+fn parse_timeout(timeout: String) -> Result<Duration, ConfigError> {
+    match timeout.parse() {
+        Ok(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+        _ => Err(ConfigError::InvalidTimeout(timeout)),
+    }
+}
+
+/// Parses `STATUS=FILE`, reading the page from `FILE`. Its content type is guessed
+/// from the extension.
+fn parse_error_page(value: String) -> Result<(HttpStatusCode, HttpContent), ConfigError> {
+    let invalid = |reason: &str| ConfigError::InvalidErrorPage(value.clone(), reason.to_string());
+    let (status, path) = value
+        .split_once('=')
+        .ok_or_else(|| invalid("expected STATUS=FILE"))?;
+    let status = status
+        .parse()
+        .ok()
+        .and_then(HttpStatusCode::from_u16)
+        .filter(|status| status.code() >= 400)
+        .ok_or_else(|| invalid("expected an error status"))?;
+    let content = std::fs::read(path).map_err(|e| invalid(&e.to_string()))?;
+
+    let page = HttpContent {
+        content,
+        content_type: content_type_for_extension(path),
+    };
+    Ok((status, page))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        Config::parse(args.iter().map(|arg| arg.to_string()), |name| {
+            env.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = parse(&[], &[]).unwrap();
+        assert_eq!(config.bind_addr(), "127.0.0.1:4221");
+        assert_eq!(config.directory, None);
+        assert_eq!(config.root, None);
+        assert!(config.log);
+        assert!(!config.strict_headers);
+        assert!(parse(&["--strict-headers"], &[]).unwrap().strict_headers);
+    }
+
+    #[test]
+    fn test_trailing_slash() {
+        assert_eq!(
+            parse(&[], &[]).unwrap().trailing_slash,
+            TrailingSlash::Strict
+        );
+        assert_eq!(
+            parse(&["--trailing-slash", "redirect"], &[])
+                .unwrap()
+                .trailing_slash,
+            TrailingSlash::Redirect
+        );
+        assert_eq!(
+            parse(&["--trailing-slash", "ignore"], &[])
+                .unwrap()
+                .trailing_slash,
+            TrailingSlash::Ignore
+        );
+        assert_eq!(
+            parse(&["--trailing-slash", "loose"], &[]),
+            Err(ConfigError::InvalidTrailingSlash(String::from("loose")))
+        );
+    }
+
+    #[test]
+    fn test_health_check() {
+        assert!(parse(&[], &[]).unwrap().health_check);
+        assert!(!parse(&["--no-health-check"], &[]).unwrap().health_check);
+    }
+
+    #[test]
+    fn test_maintenance() {
+        assert_eq!(parse(&[], &[]).unwrap().maintenance, None);
+        assert_eq!(
+            parse(&["--maintenance", "300"], &[]).unwrap().maintenance,
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            parse(&["--maintenance", "soon"], &[]),
+            Err(ConfigError::InvalidInterval(String::from("soon")))
+        );
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        assert_eq!(parse(&[], &[]).unwrap().rate_limit, None);
+        assert_eq!(
+            parse(&["--rate-limit", "10"], &[]).unwrap().rate_limit,
+            Some(10)
+        );
+        for rate in ["0", "fast"] {
+            assert_eq!(
+                parse(&["--rate-limit", rate], &[]),
+                Err(ConfigError::InvalidRateLimit(rate.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_log_format() {
+        assert_eq!(parse(&[], &[]).unwrap().log_format, LogFormat::Common);
+        assert_eq!(
+            parse(&["--log-format", "json"], &[]).unwrap().log_format,
+            LogFormat::Json
+        );
+        assert_eq!(
+            parse(&["--log-format", "xml"], &[]),
+            Err(ConfigError::InvalidLogFormat(String::from("xml")))
+        );
+    }
+
+    #[test]
+    fn test_quiet() {
+        assert!(!parse(&["--quiet"], &[]).unwrap().log);
+        assert!(!parse(&[], &[("LOG", "off")]).unwrap().log);
+        assert!(parse(&[], &[("LOG", "on")]).unwrap().log);
+    }
+
+    #[test]
+    fn test_flags_override_env() {
+        let config = parse(
+            &[
+                "--directory",
+                "/tmp/files",
+                "--port",
+                "8080",
+                "--threads",
+                "4",
+            ],
+            &[("HOST", "0.0.0.0"), ("PORT", "9000"), ("THREADS", "2")],
+        )
+        .unwrap();
+
+        assert_eq!(config.bind_addr(), "0.0.0.0:8080");
+        assert_eq!(config.directory, Some(PathBuf::from("/tmp/files")));
+        assert_eq!(config.threads, 4);
+    }
+
+    #[test]
+    fn test_ipv6_host() {
+        let config = parse(&["--host", "[::1]:4221"], &[]).unwrap();
+        assert_eq!(config.host, "::1");
+        assert_eq!(
+            config.bind_addr().parse::<SocketAddr>(),
+            Ok(SocketAddr::from((Ipv6Addr::LOCALHOST, 4221)))
+        );
+
+        let config = parse(&["--host", "::", "--port", "8080"], &[]).unwrap();
+        assert_eq!(
+            config.bind_addr().parse::<SocketAddr>(),
+            Ok(SocketAddr::from((Ipv6Addr::UNSPECIFIED, 8080)))
+        );
+
+        let config = parse(&[], &[("HOST", "[::1]")]).unwrap();
+        assert_eq!(config.bind_addr(), "[::1]:4221");
+
+        // A port given with the host is overridden by a later `--port`.
+        let config = parse(&["--host", "127.0.0.1:9000", "--port", "80"], &[]).unwrap();
+        assert_eq!(config.bind_addr(), "127.0.0.1:80");
+        assert_eq!(
+            parse(&["--host", "localhost"], &[]).unwrap().bind_addr(),
+            "localhost:4221"
+        );
+
+        assert_eq!(
+            parse(&["--host", "[::1"], &[]),
+            Err(ConfigError::InvalidHost(String::from("[::1")))
+        );
+        assert_eq!(
+            parse(&["--host", "[localhost]"], &[]),
+            Err(ConfigError::InvalidHost(String::from("[localhost]")))
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(parse(&[], &[]).unwrap().timeout, Duration::from_secs(30));
+        assert_eq!(
+            parse(&["--timeout", "5"], &[]).unwrap().timeout,
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            parse(&[], &[]).unwrap().header_timeout,
+            Duration::from_secs(20)
+        );
+        assert_eq!(
+            parse(&["--header-timeout", "2"], &[])
+                .unwrap()
+                .header_timeout,
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            parse(&["--max-body-size", "1024"], &[])
+                .unwrap()
+                .max_body_size,
+            1024
+        );
+        assert_eq!(
+            parse(&["--max-body-size", "1k"], &[]),
+            Err(ConfigError::InvalidSize(String::from("1k")))
+        );
+        let config = parse(
+            &["--max-request-line", "100", "--max-header-size", "2000"],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(config.max_request_line, 100);
+        assert_eq!(config.max_header_size, 2000);
+        assert_eq!(
+            parse(&["--timeout", "0"], &[]),
+            Err(ConfigError::InvalidTimeout(String::from("0")))
+        );
+        assert_eq!(parse(&[], &[]).unwrap().stats_interval, Duration::ZERO);
+        assert_eq!(
+            parse(&["--stats-interval", "60"], &[])
+                .unwrap()
+                .stats_interval,
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            parse(&["--stats-interval", "-1"], &[]),
+            Err(ConfigError::InvalidInterval(String::from("-1")))
+        );
+    }
+
+    #[test]
+    fn test_cache_control() {
+        assert_eq!(parse(&[], &[]).unwrap().cache_control, None);
+        assert_eq!(
+            parse(&["--cache-control", "max-age=3600"], &[])
+                .unwrap()
+                .cache_control
+                .as_deref(),
+            Some("max-age=3600")
+        );
+    }
+
+    #[test]
+    fn test_default_content_type() {
+        assert_eq!(
+            parse(&[], &[]).unwrap().default_content_type,
+            HttpContentType::ApplicationOctetStream
+        );
+        assert_eq!(
+            parse(&["--default-content-type", "Text/Plain"], &[])
+                .unwrap()
+                .default_content_type,
+            HttpContentType::TextPlain
+        );
+    }
+
+    #[test]
+    fn test_server_header() {
+        assert_eq!(parse(&[], &[]).unwrap().server.as_deref(), Some(SERVER));
+        assert_eq!(
+            parse(&["--server", "custom/1.0"], &[])
+                .unwrap()
+                .server
+                .as_deref(),
+            Some("custom/1.0")
+        );
+        assert_eq!(parse(&["--no-server"], &[]).unwrap().server, None);
+    }
+
+    #[test]
+    fn test_cors() {
+        assert_eq!(parse(&[], &[]).unwrap().cors, None);
+        assert_eq!(parse(&["--cors"], &[]).unwrap().cors, Some(Cors::default()));
+
+        let config = parse(
+            &[
+                "--cors-origin",
+                "https://a.com",
+                "--cors-origin",
+                "https://b.com",
+            ],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            config.cors.unwrap().allowed_origins,
+            ["https://a.com", "https://b.com"]
+        );
+    }
+
+    #[test]
+    fn test_validate_directory() {
+        assert_eq!(parse(&[], &[]).unwrap().validate(), Ok(()));
+
+        let dir = std::env::temp_dir();
+        let config = parse(&["--directory", dir.to_str().unwrap()], &[]).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+
+        let temp = TempDir::new("config");
+        let file = temp.join("not-a-directory");
+        std::fs::write(&file, "").unwrap();
+        let config = parse(&["--directory", file.to_str().unwrap()], &[]).unwrap();
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::InvalidDirectory(
+                file.clone(),
+                String::from("not a directory")
+            ))
+        );
+        std::fs::remove_file(&file).unwrap();
+
+        let config = parse(&["--root", file.to_str().unwrap()], &[]).unwrap();
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::InvalidDirectory(path, _)) if path == file
+        ));
+    }
+
+    #[test]
+    fn test_error_pages() {
+        let dir = TempDir::new("error-page");
+        let file = dir.join("404.html");
+        std::fs::write(&file, "<h1>Gone fishing</h1>").unwrap();
+        let arg = format!("404={}", file.display());
+
+        let config = parse(&["--error-page", &arg], &[]).unwrap();
+        let page = config.error_pages.get(HttpStatusCode::NotFound).unwrap();
+        assert_eq!(page.content, b"<h1>Gone fishing</h1>");
+        assert_eq!(page.content_type, HttpContentType::TextHtml);
+        assert!(config
+            .error_pages
+            .get(HttpStatusCode::InternalServerError)
+            .is_some());
+
+        let config = parse(&["--no-error-pages", "--error-page", &arg], &[]).unwrap();
+        assert!(config
+            .error_pages
+            .get(HttpStatusCode::InternalServerError)
+            .is_none());
+        std::fs::remove_file(&file).unwrap();
+
+        for arg in ["404", "200=page.html", "999=page.html"] {
+            assert!(matches!(
+                parse(&["--error-page", arg], &[]),
+                Err(ConfigError::InvalidErrorPage(value, _)) if value == arg
+            ));
+        }
+        assert!(matches!(
+            parse(&["--error-page", &arg], &[]),
+            Err(ConfigError::InvalidErrorPage(..))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        assert_eq!(
+            parse(&["--port", "http"], &[]),
+            Err(ConfigError::InvalidPort(String::from("http")))
+        );
+        assert_eq!(
+            parse(&[], &[("PORT", "70000")]),
+            Err(ConfigError::InvalidPort(String::from("70000")))
+        );
+        assert_eq!(
+            parse(&["--threads", "0"], &[]),
+            Err(ConfigError::InvalidThreads(String::from("0")))
+        );
+        assert_eq!(
+            parse(&["--port"], &[]),
+            Err(ConfigError::MissingValue(String::from("--port")))
+        );
+        assert_eq!(
+            parse(&["--verbose"], &[]),
+            Err(ConfigError::UnknownArgument(String::from("--verbose")))
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Cross-origin resource sharing: the `Access-Control-*` response headers that let
+//! browsers call the server from pages served by other origins.
+
+use crate::http::{HttpMethod, HttpRequest, HttpResponse};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cors {
+    /// Origins allowed to make requests, or `*` for any.
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Cors {
+            allowed_origins: vec![String::from("*")],
+        }
+    }
+}
+
+impl Cors {
+    /// Adds the CORS headers `request` calls for to `response`. Preflight requests
+    /// (`OPTIONS` with `Access-Control-Request-Method`) are also told which methods,
+    /// taken from the response's `Allow` header, and request headers may be used.
+    pub fn apply(&self, request: &HttpRequest, mut response: HttpResponse) -> HttpResponse {
+        let origin = request.header("Origin");
+
+        if self.allowed_origins.iter().any(|allowed| allowed == "*") {
+            response = response.with_header("Access-Control-Allow-Origin", "*");
+        } else if let Some(origin) = origin.filter(|origin| self.allows(origin)) {
+            response = response
+                .with_header("Access-Control-Allow-Origin", origin)
+                .with_vary("Origin");
+        } else {
+            return response;
+        }
+
+        let preflight = request.method == HttpMethod::Options
+            && request.header("Access-Control-Request-Method").is_some();
+        if preflight {
+            if let Some(allow) = response.header("Allow").map(String::from) {
+                response = response.with_header("Access-Control-Allow-Methods", &allow);
+            }
+            if let Some(headers) = request.header("Access-Control-Request-Headers") {
+                response = response.with_header("Access-Control-Allow-Headers", headers);
+            }
+        }
+
+        response
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpStatusCode;
+
+    fn apply(cors: &Cors, raw_request: &str, response: HttpResponse) -> HttpResponse {
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+        cors.apply(&request, response)
+    }
+
+    #[test]
+    fn test_any_origin() {
+        let response = apply(
+            &Cors::default(),
+            "GET / HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n",
+            HttpResponse::new(HttpStatusCode::Ok, None),
+        );
+        assert_eq!(response.header("Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(response.header("Access-Control-Allow-Methods"), None);
+    }
+
+    #[test]
+    fn test_listed_origins() {
+        let cors = Cors {
+            allowed_origins: vec![String::from("https://example.com")],
+        };
+
+        let response = apply(
+            &cors,
+            "GET / HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n",
+            HttpResponse::new(HttpStatusCode::Ok, None),
+        );
+        assert_eq!(
+            response.header("Access-Control-Allow-Origin"),
+            Some("https://example.com")
+        );
+        assert_eq!(response.header("Vary"), Some("Origin"));
+
+        let response = apply(
+            &cors,
+            "GET / HTTP/1.1\r\nOrigin: https://elsewhere.com\r\n\r\n",
+            HttpResponse::new(HttpStatusCode::Ok, None),
+        );
+        assert_eq!(response.header("Access-Control-Allow-Origin"), None);
+    }
+
+    #[test]
+    fn test_preflight() {
+        let response = apply(
+            &Cors::default(),
+            "OPTIONS /files/a.txt HTTP/1.1\r\nOrigin: https://example.com\r\nAccess-Control-Request-Method: POST\r\nAccess-Control-Request-Headers: content-type\r\n\r\n",
+            HttpResponse::new(HttpStatusCode::NoContent, None)
+                .with_header("Allow", "GET, HEAD, POST, OPTIONS"),
+        );
+        assert_eq!(response.header("Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(
+            response.header("Access-Control-Allow-Methods"),
+            Some("GET, HEAD, POST, OPTIONS")
+        );
+        assert_eq!(
+            response.header("Access-Control-Allow-Headers"),
+            Some("content-type")
+        );
+    }
+}
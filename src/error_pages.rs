@@ -0,0 +1,138 @@
+//! Bodies for error responses that don't carry one, so a browser shows a page saying
+//! what went wrong instead of a blank one.
+
+use crate::http::{BodySource, HttpContent, HttpContentType, HttpResponse, HttpStatusCode};
+use crate::template::render;
+use std::collections::HashMap;
+
+const BUILT_IN_PAGE: &str = "<!DOCTYPE html>\n<html>\n<head><title>{{status}}</title></head>\n<body>\n<h1>{{status}}</h1>\n</body>\n</html>\n";
+
+/// Pages to send by status code. The default has built-in HTML pages for
+/// `404 Not Found` and `500 Internal Server Error`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorPages {
+    pages: HashMap<u16, HttpContent>,
+}
+
+impl Default for ErrorPages {
+    fn default() -> Self {
+        let mut pages = ErrorPages::empty();
+        for status in [
+            HttpStatusCode::NotFound,
+            HttpStatusCode::InternalServerError,
+        ] {
+            pages.insert(status, built_in_page(status));
+        }
+        pages
+    }
+}
+
+impl ErrorPages {
+    /// No pages at all, so error responses are sent as they are.
+    pub fn empty() -> ErrorPages {
+        ErrorPages {
+            pages: HashMap::new(),
+        }
+    }
+
+    /// Sends `page` for `status` responses from now on, replacing any page it had.
+    pub fn insert(&mut self, status: HttpStatusCode, page: HttpContent) {
+        self.pages.insert(status.code(), page);
+    }
+
+    pub fn get(&self, status: HttpStatusCode) -> Option<&HttpContent> {
+        self.pages.get(&status.code())
+    }
+
+    /// Gives `response` the page for its status if there is one and the response has
+    /// no body of its own.
+    pub fn apply(&self, mut response: HttpResponse) -> HttpResponse {
+        let has_body = response
+            .content
+            .as_ref()
+            .is_some_and(|content| !content.content.is_empty());
+        if has_body {
+            return response;
+        }
+
+        if let Some(page) = self.get(response.status_code) {
+            response.content = Some(HttpContent {
+                content: BodySource::Bytes(page.content.clone()),
+                content_type: page.content_type.clone(),
+            });
+        }
+        response
+    }
+}
+
+fn built_in_page(status: HttpStatusCode) -> HttpContent {
+    let vars = HashMap::from([("status", status.to_string())]);
+    HttpContent {
+        content: render(BUILT_IN_PAGE, &vars).into_bytes(),
+        content_type: HttpContentType::TextHtml,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(response: &HttpResponse) -> &[u8] {
+        response
+            .content
+            .as_ref()
+            .and_then(|content| content.content.as_bytes())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_built_in_pages() {
+        let pages = ErrorPages::default();
+
+        let response = pages.apply(HttpResponse::new(HttpStatusCode::NotFound, None));
+        let content = response.content.as_ref().unwrap();
+        assert_eq!(content.content_type, HttpContentType::TextHtml);
+        let html = String::from_utf8(body(&response).to_vec()).unwrap();
+        assert!(html.contains("<title>404 Not Found</title>"));
+
+        let response = pages.apply(HttpResponse::new(HttpStatusCode::InternalServerError, None));
+        assert!(
+            String::from_utf8_lossy(body(&response)).contains("<h1>500 Internal Server Error</h1>")
+        );
+
+        let response = pages.apply(HttpResponse::new(HttpStatusCode::BadRequest, None));
+        assert!(response.content.is_none());
+    }
+
+    #[test]
+    fn test_configured_page() {
+        let mut pages = ErrorPages::empty();
+        pages.insert(
+            HttpStatusCode::NotFound,
+            HttpContent {
+                content: b"nothing here".to_vec(),
+                content_type: HttpContentType::TextPlain,
+            },
+        );
+
+        let response = pages.apply(HttpResponse::new(HttpStatusCode::NotFound, None));
+        assert_eq!(body(&response), b"nothing here");
+        assert_eq!(
+            response.content.unwrap().content_type,
+            HttpContentType::TextPlain
+        );
+
+        // A response that already has a body keeps it.
+        let response = pages.apply(HttpResponse::new(
+            HttpStatusCode::NotFound,
+            Some(HttpContent {
+                content: b"no such user".to_vec(),
+                content_type: HttpContentType::TextPlain,
+            }),
+        ));
+        assert_eq!(body(&response), b"no such user");
+
+        let response = ErrorPages::empty().apply(HttpResponse::new(HttpStatusCode::NotFound, None));
+        assert!(response.content.is_none());
+    }
+}
@@ -0,0 +1,536 @@
+use crate::date::parse_http_date;
+use crate::http::HttpContentType;
+use crate::template::render;
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, PartialEq)]
+pub enum PathError {
+    /// The requested path resolves to somewhere outside the base directory.
+    Forbidden,
+    /// The requested path, or the directory it would be created in, doesn't exist.
+    NotFound,
+}
+
+/// Resolves `requested` against `base`, following `..` components and symlinks, and
+/// makes sure the result is still inside `base`.
+///
+/// The file itself doesn't need to exist, so that it can be created, but its parent
+/// directory does. A symlink whose target doesn't exist is forbidden, since whatever
+/// opened it to write would create its target, wherever that is.
+pub fn resolve_path(base: &Path, requested: &str) -> Result<PathBuf, PathError> {
+    let base = base.canonicalize().map_err(not_found)?;
+    let joined = base.join(requested);
+
+    let resolved = match joined.canonicalize() {
+        Ok(resolved) => resolved,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let file_name = match joined.components().next_back() {
+                Some(Component::Normal(file_name)) => file_name.to_owned(),
+                _ => return Err(PathError::Forbidden),
+            };
+            let parent = joined.parent().ok_or(PathError::Forbidden)?;
+            let resolved = parent.canonicalize().map_err(not_found)?.join(file_name);
+
+            // Canonicalizing fails on a dangling symlink just as on a missing file.
+            match resolved.symlink_metadata() {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => resolved,
+                _ => return Err(PathError::Forbidden),
+            }
+        }
+        Err(_) => return Err(PathError::NotFound),
+    };
+
+    if resolved.starts_with(&base) {
+        Ok(resolved)
+    } else {
+        Err(PathError::Forbidden)
+    }
+}
+
+/// Picks the content type to serve a file with from its extension, falling back to
+/// `application/octet-stream` for anything unrecognised.
+pub fn content_type_for_extension(path: &str) -> HttpContentType {
+    known_content_type(path).unwrap_or(HttpContentType::ApplicationOctetStream)
+}
+
+/// The content type for a file's extension, or `None` if it has none or it isn't one
+/// the server knows.
+pub fn known_content_type(path: &str) -> Option<HttpContentType> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    let content_type = match extension.as_deref()? {
+        "html" | "htm" => HttpContentType::TextHtml,
+        "css" => HttpContentType::TextCss,
+        "js" | "mjs" => HttpContentType::ApplicationJavascript,
+        "json" => HttpContentType::ApplicationJson,
+        "png" => HttpContentType::ImagePng,
+        "jpg" | "jpeg" => HttpContentType::ImageJpeg,
+        "gif" => HttpContentType::ImageGif,
+        "svg" => HttpContentType::ImageSvg,
+        "txt" => HttpContentType::TextPlain,
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+/// The outcome of applying a `Range` header to a file of known length.
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    /// The inclusive byte range `start..=end` to send.
+    Partial { start: u64, end: u64 },
+    /// The range lies entirely past the end of the file.
+    Unsatisfiable,
+}
+
+/// Parses a single-range `Range` header such as `bytes=0-1023`, `bytes=500-` or
+/// `bytes=-500` against a file of `len` bytes. Returns `None` for headers that should be
+/// ignored in favour of sending the whole file, including multi-range requests.
+pub fn parse_range(header: &str, len: u64) -> Option<ByteRange> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 || len == 0 {
+            return Some(ByteRange::Unsatisfiable);
+        }
+        (len.saturating_sub(suffix), len - 1)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = match end {
+            "" => u64::MAX,
+            end => end.parse().ok()?,
+        };
+        if end < start {
+            return None;
+        }
+        if start >= len {
+            return Some(ByteRange::Unsatisfiable);
+        }
+        (start, end.min(len - 1))
+    };
+
+    Some(ByteRange::Partial {
+        start: range.0,
+        end: range.1,
+    })
+}
+
+/// Builds a weak entity tag for a file from its size and modification time, which
+/// change whenever its contents are rewritten.
+pub fn weak_etag(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+
+    format!(
+        "W/\"{:x}-{:x}.{:x}\"",
+        metadata.len(),
+        modified.as_secs(),
+        modified.subsec_nanos()
+    )
+}
+
+/// Returns whether an `If-None-Match` header value matches `etag`, comparing weakly
+/// as conditional GETs do: `W/"x"` and `"x"` are the same tag.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+
+    if_none_match.trim() == "*" || if_none_match.split(',').any(|tag| opaque(tag) == etag)
+}
+
+/// Returns whether a file last modified at `modified` is unchanged since the
+/// `If-Modified-Since` date, which is ignored when unparseable or later than `now`.
+pub fn not_modified_since(modified: SystemTime, if_modified_since: &str, now: SystemTime) -> bool {
+    let Some(since) = parse_http_date(if_modified_since).filter(|&since| since <= now) else {
+        return false;
+    };
+    // Header dates have one-second resolution, so compare whole seconds.
+    let secs = |time: SystemTime| time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+
+    secs(modified) <= secs(since)
+}
+
+/// Returns whether an `If-Range` validator still describes the file with `etag`, last
+/// modified at `modified`, so that a `Range` sent with it can be honoured at `now`.
+///
+/// Only strong validators count: an entity tag has to be strong and exactly the
+/// current one, and a date exactly the modification time, to the second, which also
+/// has to be at least a second before `now` so that the file can't have changed again
+/// within it.
+pub fn if_range_matches(
+    if_range: &str,
+    etag: &str,
+    modified: Option<SystemTime>,
+    now: SystemTime,
+) -> bool {
+    let if_range = if_range.trim();
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return !if_range.starts_with("W/") && !etag.starts_with("W/") && if_range == etag;
+    }
+
+    let secs = |time: SystemTime| time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    modified
+        .zip(parse_http_date(if_range))
+        .is_some_and(|(modified, date)| {
+            secs(modified) == secs(date) && modified + Duration::from_secs(1) <= now
+        })
+}
+
+/// Returns the `index.html` to serve in place of `dir`, if `dir` is a directory that
+/// has one.
+pub fn index_file(dir: &Path) -> Option<PathBuf> {
+    let index = dir.join("index.html");
+    (dir.is_dir() && index.is_file()).then_some(index)
+}
+
+const LISTING_HEAD: &str = "<!DOCTYPE html>\n<html>\n<head><title>Index of {{path}}</title></head>\n<body>\n<h1>Index of {{path}}</h1>\n<ul>\n";
+const LISTING_ENTRY: &str = "<li><a href=\"{{href}}\">{{name}}</a></li>\n";
+
+/// Renders an HTML page linking to each entry of `dir`, sorted by name with
+/// directories marked by a trailing slash. Dotfiles are left out. `url_path` is the
+/// path the listing is served at, which the links are made relative to.
+pub fn directory_listing(dir: &Path, url_path: &str) -> io::Result<String> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let is_dir = entry.file_type()?.is_dir();
+        entries.push((name, is_dir));
+    }
+    entries.sort();
+
+    let base = if url_path.ends_with('/') {
+        url_path.to_string()
+    } else {
+        format!("{}/", url_path)
+    };
+    let mut html = render(LISTING_HEAD, &HashMap::from([("path", base.clone())]));
+    for (name, is_dir) in entries {
+        let suffix = if is_dir { "/" } else { "" };
+        let vars = HashMap::from([
+            (
+                "href",
+                format!("{}{}{}", encode_path(&base), encode_path(&name), suffix),
+            ),
+            ("name", format!("{}{}", name, suffix)),
+        ]);
+        html.push_str(&render(LISTING_ENTRY, &vars));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+
+    Ok(html)
+}
+
+/// Builds a `Content-Disposition` value telling the browser to save the response as
+/// `filename`. Quotes and backslashes are escaped in the quoted name, and anything
+/// outside printable ASCII replaced; when that loses something, the exact name follows
+/// encoded as UTF-8 for browsers that understand `filename*`.
+pub fn content_disposition(filename: &str) -> String {
+    let mut quoted = String::with_capacity(filename.len());
+    for c in filename.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            ' '..='~' => quoted.push(c),
+            _ => quoted.push('_'),
+        }
+    }
+
+    if filename.chars().all(|c| matches!(c, ' '..='~')) {
+        format!("attachment; filename=\"{}\"", quoted)
+    } else {
+        format!(
+            "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+            quoted,
+            encode_path(filename)
+        )
+    }
+}
+
+/// Percent-encodes everything in `path` but unreserved characters and slashes, so a
+/// file name can be used in a link whatever it contains.
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn not_found(_: io::Error) -> PathError {
+    PathError::NotFound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches("W/\"5-1\"", "W/\"5-1\""));
+        assert!(etag_matches("\"5-1\"", "W/\"5-1\""));
+        assert!(etag_matches("\"a\", W/\"5-1\"", "W/\"5-1\""));
+        assert!(etag_matches("*", "W/\"5-1\""));
+        assert!(!etag_matches("W/\"5-2\"", "W/\"5-1\""));
+    }
+
+    #[test]
+    fn test_if_range_matches() {
+        let mtime = UNIX_EPOCH + Duration::from_millis(784_111_777_500);
+        let modified = Some(mtime);
+        let now = SystemTime::now();
+
+        assert!(if_range_matches("\"5-1\"", "\"5-1\"", modified, now));
+        assert!(!if_range_matches("\"5-2\"", "\"5-1\"", modified, now));
+        // Weak entity tags never match, not even themselves.
+        assert!(!if_range_matches("W/\"5-1\"", "W/\"5-1\"", modified, now));
+        assert!(!if_range_matches("W/\"5-1\"", "\"5-1\"", modified, now));
+        assert!(!if_range_matches("\"5-1\"", "W/\"5-1\"", modified, now));
+
+        let date = "Sun, 06 Nov 1994 08:49:37 GMT";
+        assert!(if_range_matches(date, "W/\"5-1\"", modified, now));
+        assert!(!if_range_matches(
+            "Sun, 06 Nov 1994 08:49:38 GMT",
+            "W/\"5-1\"",
+            modified,
+            now
+        ));
+        assert!(!if_range_matches(date, "W/\"5-1\"", None, now));
+        assert!(!if_range_matches("garbage", "W/\"5-1\"", modified, now));
+
+        // A date is only strong once its second has passed.
+        for now in [mtime, mtime + Duration::from_millis(499)] {
+            assert!(!if_range_matches(date, "W/\"5-1\"", modified, now));
+        }
+        let now = mtime + Duration::from_secs(1);
+        assert!(if_range_matches(date, "W/\"5-1\"", modified, now));
+    }
+
+    #[test]
+    fn test_not_modified_since() {
+        let modified = UNIX_EPOCH + Duration::from_millis(784_111_777_500);
+        let now = UNIX_EPOCH + Duration::from_secs(1700000000);
+
+        assert!(not_modified_since(
+            modified,
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            now
+        ));
+        assert!(not_modified_since(
+            modified,
+            "Mon, 07 Nov 1994 08:49:37 GMT",
+            now
+        ));
+        assert!(!not_modified_since(
+            modified,
+            "Sun, 06 Nov 1994 08:49:36 GMT",
+            now
+        ));
+        assert!(!not_modified_since(modified, "not a date", now));
+        assert!(!not_modified_since(
+            modified,
+            "Sun, 06 Nov 2050 08:49:37 GMT",
+            now
+        ));
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("report.pdf"),
+            "attachment; filename=\"report.pdf\""
+        );
+        assert_eq!(
+            content_disposition("say \"hi\".txt"),
+            "attachment; filename=\"say \\\"hi\\\".txt\""
+        );
+        assert_eq!(
+            content_disposition("caf\u{e9}\r\n.txt"),
+            "attachment; filename=\"caf___.txt\"; filename*=UTF-8''caf%C3%A9%0D%0A.txt"
+        );
+    }
+
+    #[test]
+    fn test_directory_listing() {
+        let dir = TempDir::new("listing");
+        fs::write(dir.join("report.txt"), "").unwrap();
+        fs::write(dir.join("a <b>.txt"), "").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+
+        let html = directory_listing(&dir, "/files/").unwrap();
+        assert!(html.contains("<title>Index of /files/</title>"));
+        assert!(html.contains("<li><a href=\"/files/report.txt\">report.txt</a></li>"));
+        assert!(html.contains("<li><a href=\"/files/a%20%3Cb%3E.txt\">a &lt;b&gt;.txt</a></li>"));
+        assert!(html.contains("<li><a href=\"/files/sub/\">sub/</a></li>"));
+        assert!(!html.contains(".hidden"));
+
+        let html = directory_listing(&dir.join("sub"), "/files/sub").unwrap();
+        assert!(html.contains("<h1>Index of /files/sub/</h1>"));
+    }
+
+    #[test]
+    fn test_resolve_path_inside_base() {
+        let dir = TempDir::new("resolve-inside");
+        let base = dir.join("root");
+        fs::create_dir_all(base.join("sub")).unwrap();
+        fs::write(base.join("sub/file.txt"), "hello").unwrap();
+        let base_canonical = base.canonicalize().unwrap();
+
+        assert_eq!(
+            resolve_path(&base, "sub/file.txt"),
+            Ok(base_canonical.join("sub/file.txt"))
+        );
+        assert_eq!(
+            resolve_path(&base, "sub/../sub/file.txt"),
+            Ok(base_canonical.join("sub/file.txt"))
+        );
+        assert_eq!(
+            resolve_path(&base, "new.txt"),
+            Ok(base_canonical.join("new.txt"))
+        );
+        assert_eq!(
+            resolve_path(&base, "missing/new.txt"),
+            Err(PathError::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_resolve_path_rejects_traversal() {
+        let dir = TempDir::new("resolve-traversal");
+        let base = dir.join("root");
+        fs::create_dir_all(&base).unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+
+        assert_eq!(
+            resolve_path(&base, "../secret.txt"),
+            Err(PathError::Forbidden)
+        );
+        assert_eq!(
+            resolve_path(&base, "../../../../etc/passwd"),
+            Err(PathError::Forbidden)
+        );
+        assert_eq!(resolve_path(&base, "../new.txt"), Err(PathError::Forbidden));
+        assert_eq!(resolve_path(&base, ".."), Err(PathError::Forbidden));
+        assert_eq!(
+            resolve_path(&base, dir.join("secret.txt").to_str().unwrap()),
+            Err(PathError::Forbidden)
+        );
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let partial = |start, end| Some(ByteRange::Partial { start, end });
+
+        assert_eq!(parse_range("bytes=0-1023", 2048), partial(0, 1023));
+        assert_eq!(parse_range("bytes=1000-5000", 2048), partial(1000, 2047));
+        assert_eq!(parse_range("bytes=1000-", 2048), partial(1000, 2047));
+        assert_eq!(parse_range("bytes=-48", 2048), partial(2000, 2047));
+        assert_eq!(parse_range("bytes=-5000", 2048), partial(0, 2047));
+        assert_eq!(
+            parse_range("bytes=2048-", 2048),
+            Some(ByteRange::Unsatisfiable)
+        );
+        assert_eq!(
+            parse_range("bytes=-0", 2048),
+            Some(ByteRange::Unsatisfiable)
+        );
+        assert_eq!(parse_range("bytes=10-5", 2048), None);
+        assert_eq!(parse_range("bytes=0-1,5-9", 2048), None);
+        assert_eq!(parse_range("items=0-1", 2048), None);
+        assert_eq!(parse_range("bytes=a-b", 2048), None);
+    }
+
+    #[test]
+    fn test_content_type_for_extension() {
+        assert_eq!(
+            content_type_for_extension("index.html"),
+            HttpContentType::TextHtml
+        );
+        assert_eq!(
+            content_type_for_extension("css/site.CSS"),
+            HttpContentType::TextCss
+        );
+        assert_eq!(
+            content_type_for_extension("app.js"),
+            HttpContentType::ApplicationJavascript
+        );
+        assert_eq!(
+            content_type_for_extension("data.json"),
+            HttpContentType::ApplicationJson
+        );
+        assert_eq!(
+            content_type_for_extension("logo.png"),
+            HttpContentType::ImagePng
+        );
+        assert_eq!(
+            content_type_for_extension("photo.JPG"),
+            HttpContentType::ImageJpeg
+        );
+        assert_eq!(
+            content_type_for_extension("icon.svg"),
+            HttpContentType::ImageSvg
+        );
+        assert_eq!(
+            content_type_for_extension("archive.tar.gz"),
+            HttpContentType::ApplicationOctetStream
+        );
+        assert_eq!(
+            content_type_for_extension("Makefile"),
+            HttpContentType::ApplicationOctetStream
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_path_rejects_symlink_outside_base() {
+        let dir = TempDir::new("resolve-symlink");
+        let base = dir.join("root");
+        fs::create_dir_all(&base).unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(dir.join("secret.txt"), base.join("link.txt")).unwrap();
+
+        assert_eq!(resolve_path(&base, "link.txt"), Err(PathError::Forbidden));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_path_rejects_dangling_symlink() {
+        let dir = TempDir::new("resolve-dangling");
+        let base = dir.join("root");
+        fs::create_dir_all(&base).unwrap();
+        std::os::unix::fs::symlink(dir.join("outside.txt"), base.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(base.join("missing.txt"), base.join("inside.txt")).unwrap();
+
+        assert_eq!(resolve_path(&base, "link.txt"), Err(PathError::Forbidden));
+        assert_eq!(resolve_path(&base, "inside.txt"), Err(PathError::Forbidden));
+        assert!(!dir.join("outside.txt").exists());
+    }
+}
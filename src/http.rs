@@ -1,241 +1,2457 @@
+use crate::compression;
+use crate::date::format_http_date;
+use crate::json::JsonValue;
 use core::fmt;
-use std::{collections::HashMap, str::FromStr};
+use itertools::Itertools;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::SocketAddr;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ParseError {
+    #[error("missing request start line")]
+    MissingStartLine,
+    #[error("unknown HTTP method: {0}")]
+    UnknownMethod(String),
+    #[error("malformed request line")]
+    MalformedStartLine,
+    #[error("invalid request target: {0:?}")]
+    InvalidTarget(String),
+    #[error("missing HTTP version in request line")]
+    MissingVersion,
+    #[error("unsupported HTTP version: {0}")]
+    UnsupportedVersion(String),
+    #[error("missing blank line after the request headers")]
+    MissingHeaderSeparator,
+    #[error("request headers are not valid UTF-8")]
+    InvalidHeaderEncoding,
+    #[error("request has a body but no Content-Length")]
+    MissingContentLength,
+    #[error("invalid Content-Length")]
+    InvalidContentLength,
+    #[error("request body is {actual} bytes but Content-Length is {expected}")]
+    IncompleteBody { expected: usize, actual: usize },
+    #[error("invalid chunk size in chunked request body")]
+    InvalidChunkSize,
+    #[error("chunked request body ended before its final chunk")]
+    IncompleteChunkedBody,
+    #[error("HTTP/1.1 request without a Host header")]
+    MissingHost,
+    #[error("malformed header line: {0:?}")]
+    MalformedHeader(String),
+}
+
+/// Query parameters by name, keeping every value of a repeated key.
+pub type QueryParams = HashMap<String, Vec<String>>;
+
+/// Media type parameters like `charset`, by lowercased name.
+pub type MediaParams = HashMap<String, String>;
 
 #[derive(Debug, PartialEq)]
 pub struct HttpRequest {
     pub method: HttpMethod,
+    /// The target as it appeared in the request line.
+    pub target: RequestTarget,
+    /// The percent-decoded path the target names, or `*` for [`RequestTarget::Asterisk`].
     pub path: String,
-    pub headers: std::collections::HashMap<String, String>,
+    pub query: QueryParams,
+    pub version: HttpVersion,
+    pub headers: HttpHeaders,
     pub content: Option<HttpContent>,
+    /// The address of the client that sent the request, if it's known. Parsing leaves
+    /// it `None`; the server fills it in from the connection.
+    pub remote_addr: Option<SocketAddr>,
 }
 
 impl HttpRequest {
-    pub fn new(raw_request: &str) -> HttpRequest {
-        match raw_request.split_once("\r\n\r\n") {
-            Some((headers, body)) => {
-                let lines: Vec<&str> = headers.split("\r\n").collect();
-                let (method, path) = Self::parse_start_line(lines[0]);
-                let headers = Self::parse_headers(&lines[1..]);
-
-                let content = Self::parse_content(body);
-
-                HttpRequest {
-                    method,
-                    path,
-                    headers,
-                    content,
+    /// Parses a request, ignoring any header line that isn't `name: value`.
+    pub fn new(raw_request: &[u8]) -> Result<HttpRequest, ParseError> {
+        Self::parse(raw_request, false)
+    }
+
+    /// Parses a request like [`HttpRequest::new`], but rejects one with a header line
+    /// that isn't `name: value` instead of ignoring the line.
+    pub fn new_strict(raw_request: &[u8]) -> Result<HttpRequest, ParseError> {
+        Self::parse(raw_request, true)
+    }
+
+    fn parse(raw_request: &[u8], strict: bool) -> Result<HttpRequest, ParseError> {
+        let header_end = find_header_end(raw_request).ok_or(ParseError::MissingHeaderSeparator)?;
+        let head = std::str::from_utf8(&raw_request[..header_end])
+            .map_err(|_| ParseError::InvalidHeaderEncoding)?;
+        let body = &raw_request[header_end..];
+
+        let start_line = head.lines().next().unwrap_or_default();
+        let (method, target, path, query, version) = Self::parse_start_line(start_line)?;
+        let headers = parse_header_block(&raw_request[..header_end], strict)?;
+
+        let content = Self::parse_content(&headers, body)?;
+
+        Ok(HttpRequest {
+            method,
+            target,
+            path,
+            query,
+            version,
+            headers,
+            content,
+            remote_addr: None,
+        })
+    }
+
+    /// Returns the first value of the query parameter `name`.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query_all(name).first().map(|value| value.as_str())
+    }
+
+    /// Returns every value of the query parameter `name`, in the order they were sent.
+    pub fn query_all(&self, name: &str) -> &[String] {
+        self.query.get(name).map_or(&[], |values| values.as_slice())
+    }
+
+    /// Parses the method, request target and version, splitting the target into its
+    /// percent-decoded path and query parameters.
+    fn parse_start_line(
+        line: &str,
+    ) -> Result<(HttpMethod, RequestTarget, String, QueryParams, HttpVersion), ParseError> {
+        let res: Vec<&str> = line.split(' ').collect();
+        if res.len() < 2 || res[0].is_empty() {
+            return Err(ParseError::MissingStartLine);
+        }
+        if res.len() > 3 {
+            return Err(ParseError::MalformedStartLine);
+        }
+
+        let method = HttpMethod::from_str(res[0])
+            .map_err(|_| ParseError::UnknownMethod(res[0].to_string()))?;
+
+        let target = RequestTarget::parse(&method, res[1])
+            .ok_or_else(|| ParseError::InvalidTarget(res[1].to_string()))?;
+        let path_and_query = target.path_and_query();
+        let (path, query) = match path_and_query.split_once('?') {
+            Some((path, query)) => (path, parse_form_urlencoded(query)),
+            None => (path_and_query, HashMap::new()),
+        };
+        let path = percent_decode(path).into_owned();
+
+        let version = res.get(2).ok_or(ParseError::MissingVersion)?;
+        let version = HttpVersion::from_str(version)
+            .map_err(|_| ParseError::UnsupportedVersion(version.to_string()))?;
+
+        Ok((method, target, path, query, version))
+    }
+
+    /// Decodes an `application/x-www-form-urlencoded` body, as HTML forms post. Returns
+    /// `None` for a body of any other type or one that isn't valid UTF-8.
+    pub fn form(&self) -> Option<QueryParams> {
+        let content = self.content.as_ref()?;
+        if content.content_type != HttpContentType::ApplicationFormUrlencoded {
+            return None;
+        }
+        std::str::from_utf8(&content.content)
+            .ok()
+            .map(parse_form_urlencoded)
+    }
+
+    /// The request body, empty if there isn't one.
+    pub fn body_bytes(&self) -> &[u8] {
+        self.content
+            .as_ref()
+            .map_or(&[], |content| content.content.as_slice())
+    }
+
+    /// The request body as text, or `None` if it isn't valid UTF-8.
+    pub fn body_str(&self) -> Option<&str> {
+        std::str::from_utf8(self.body_bytes()).ok()
+    }
+
+    /// Parses the `Content-Type` header into the media type and its parameters, such
+    /// as the `boundary` of `multipart/form-data`.
+    pub fn content_type(&self) -> Option<(HttpContentType, MediaParams)> {
+        self.header("Content-Type").map(parse_media_type)
+    }
+
+    /// Whether the client wants the connection kept open after this request: HTTP/1.1
+    /// connections stay open unless it sends `Connection: close`, while HTTP/1.0 ones
+    /// only do when it sends `Connection: keep-alive`.
+    pub fn is_keep_alive(&self) -> bool {
+        let has_token = |token: &str| {
+            self.header("Connection").is_some_and(|connection| {
+                connection
+                    .split(',')
+                    .any(|t| t.trim().eq_ignore_ascii_case(token))
+            })
+        };
+
+        if has_token("close") {
+            false
+        } else {
+            self.version == HttpVersion::Http11 || has_token("keep-alive")
+        }
+    }
+
+    /// Looks up a header case-insensitively, returning its first value.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// Returns every value of a header sent on several lines, in the order they were
+    /// sent.
+    pub fn header_all(&self, name: &str) -> &[String] {
+        self.headers.get_all(name)
+    }
+
+    /// Parses the header lines. A line starting with whitespace continues the value on
+    /// the line before, an obsolete folding that's joined up with a single space.
+    fn parse_headers(headers: &[&str], strict: bool) -> Result<HttpHeaders, ParseError> {
+        let mut fields: Vec<(&str, String)> = Vec::new();
+
+        // The last line is the blank one ending the headers.
+        for line in headers.iter().filter(|line| !line.is_empty()) {
+            if line.starts_with([' ', '\t']) {
+                match fields.last_mut() {
+                    Some((_, value)) => {
+                        let continued = line.trim();
+                        if !continued.is_empty() {
+                            if !value.is_empty() {
+                                value.push(' ');
+                            }
+                            value.push_str(continued);
+                        }
+                    }
+                    None if strict => return Err(ParseError::MalformedHeader(line.to_string())),
+                    None => {}
                 }
+            } else if let Some((header, value)) = line.split_once(':') {
+                fields.push((header, value.trim().to_string()));
+            } else if strict {
+                return Err(ParseError::MalformedHeader(line.to_string()));
             }
-            None => panic!("unexpected HTTP request format"),
         }
+
+        let mut headers_map = HttpHeaders::default();
+        for (header, value) in fields {
+            headers_map.insert(header, &value);
+        }
+        Ok(headers_map)
     }
 
-    fn parse_start_line(line: &str) -> (HttpMethod, String) {
-        let res: Vec<&str> = line.split(' ').collect();
+    /// Decodes a `Transfer-Encoding: chunked` body, or otherwise takes exactly
+    /// `Content-Length` bytes of `body` as the request content, so the start of a
+    /// pipelined request after it is ignored. A body shorter than declared is rejected,
+    /// and so is any body at all without a `Content-Length`.
+    fn parse_content(
+        headers: &HttpHeaders,
+        body: &[u8],
+    ) -> Result<Option<HttpContent>, ParseError> {
+        let chunked = headers.get("Transfer-Encoding").is_some_and(is_chunked);
 
-        return (
-            HttpMethod::from_str(res[0]).expect("Unexpected HTTP method"),
-            res[1].to_string(),
-        );
+        let content = if chunked {
+            decode_chunked(body)?
+        } else {
+            match Self::parse_content_length(headers, body)? {
+                Some(content) => content.to_vec(),
+                None => return Ok(None),
+            }
+        };
+
+        if content.is_empty() {
+            return Ok(None);
+        }
+
+        let content_type = headers
+            .get("Content-Type")
+            .map_or(HttpContentType::TextPlain, |content_type| {
+                parse_media_type(content_type).0
+            });
+
+        Ok(Some(HttpContent {
+            content,
+            content_type,
+        }))
     }
 
-    fn parse_headers(headers: &[&str]) -> HashMap<String, String> {
-        let mut headers_map = HashMap::new();
+    fn parse_content_length<'a>(
+        headers: &HttpHeaders,
+        body: &'a [u8],
+    ) -> Result<Option<&'a [u8]>, ParseError> {
+        let content_length = match content_length(headers)? {
+            Some(length) => length,
+            None if body.is_empty() => return Ok(None),
+            None => return Err(ParseError::MissingContentLength),
+        };
 
-        for line in headers {
-            line.split_once(":").and_then(|(header, value)| {
-                headers_map.insert(header.to_string(), value.trim().to_string())
+        if body.len() < content_length {
+            return Err(ParseError::IncompleteBody {
+                expected: content_length,
+                actual: body.len(),
             });
         }
 
-        return headers_map;
+        Ok(Some(&body[..content_length]))
     }
+}
 
-    fn parse_content(content: &str) -> Option<HttpContent> {
-        if content.is_empty() {
-            return None;
-        } else {
-            return Some(HttpContent {
-                content: content.to_string(),
-                content_type: HttpContentType::TextPlain,
-            });
+/// Parses the header lines of a request head: everything up to the blank line ending
+/// it, request line included. This is the same parse [`HttpRequest::new`] and
+/// [`HttpRequest::new_strict`] do, for code that needs the headers before the body.
+pub fn parse_header_block(head: &[u8], strict: bool) -> Result<HttpHeaders, ParseError> {
+    let head = std::str::from_utf8(head).map_err(|_| ParseError::InvalidHeaderEncoding)?;
+    let lines: Vec<&str> = head.lines().skip(1).collect();
+    HttpRequest::parse_headers(&lines, strict)
+}
+
+/// The body length `Content-Length` declares, or `None` without one. The header may be
+/// repeated, or hold a comma-separated list, as long as every value is the same.
+pub fn content_length(headers: &HttpHeaders) -> Result<Option<usize>, ParseError> {
+    let mut length = None;
+    for value in headers
+        .get_all("Content-Length")
+        .iter()
+        .flat_map(|value| value.split(','))
+    {
+        let value = value
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| ParseError::InvalidContentLength)?;
+        if length.is_some_and(|length| length != value) {
+            return Err(ParseError::InvalidContentLength);
+        }
+        length = Some(value);
+    }
+    Ok(length)
+}
+
+/// Splits a `Content-Type` value like `text/plain; charset="utf-8"` into the media
+/// type and its parameters, unquoting quoted values.
+pub fn parse_media_type(value: &str) -> (HttpContentType, MediaParams) {
+    let (media_type, mut rest) = value.split_once(';').unwrap_or((value, ""));
+    let content_type = media_type
+        .trim()
+        .parse()
+        .unwrap_or(HttpContentType::Other(media_type.trim().to_string()));
+
+    let mut params = MediaParams::new();
+    while !rest.is_empty() {
+        let name_end = rest.find(['=', ';']).unwrap_or(rest.len());
+        let name = rest[..name_end].trim();
+        rest = &rest[name_end..];
+
+        let value = match rest.strip_prefix('=').map(str::trim_start) {
+            Some(after) => match after.strip_prefix('"') {
+                Some(quoted) => {
+                    let (value, after) = unquote(quoted);
+                    rest = after;
+                    value
+                }
+                None => {
+                    let end = after.find(';').unwrap_or(after.len());
+                    rest = &after[end..];
+                    after[..end].trim_end().to_string()
+                }
+            },
+            None => String::new(),
         };
+
+        if !name.is_empty() {
+            params.insert(name.to_ascii_lowercase(), value);
+        }
+        rest = rest.split_once(';').map_or("", |(_, rest)| rest);
     }
+
+    (content_type, params)
 }
 
-#[derive(Debug, PartialEq)]
-pub enum HttpMethod {
-    Get,
-    Post,
+/// Reads a quoted string whose opening quote has been stripped, returning its unescaped
+/// contents and whatever follows the closing quote.
+fn unquote(quoted: &str) -> (String, &str) {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return (value, &quoted[i + 1..]),
+            '\\' => value.extend(chars.next().map(|(_, c)| c)),
+            c => value.push(c),
+        }
+    }
+    (value, "")
 }
 
-impl FromStr for HttpMethod {
-    type Err = ();
+/// The forms a request target can take (RFC 9112, section 3.2), apart from the
+/// authority form only `CONNECT` uses, which the server doesn't support.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RequestTarget {
+    /// A path with an optional query, as sent to an origin server: `/where?q=now`.
+    Origin(String),
+    /// `*`, with which `OPTIONS` asks about the server as a whole.
+    Asterisk,
+    /// A full URL, as sent to proxies: `http://example.com/where?q=now`.
+    Absolute(Uri),
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "GET" => Ok(HttpMethod::Get),
-            "POST" => Ok(HttpMethod::Post),
-            _ => Err(()),
+/// An absolute `http` or `https` URL, split into the parts a request cares about.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Uri {
+    pub scheme: String,
+    /// The host and optional port.
+    pub authority: String,
+    /// The path, `/` if the URL had none, followed by any query.
+    pub path_and_query: String,
+}
+
+impl RequestTarget {
+    /// Checks a request target. Anything that isn't a path or an `http` or `https`
+    /// URL is rejected, apart from the `*` that `OPTIONS` may ask about.
+    fn parse(method: &HttpMethod, target: &str) -> Option<RequestTarget> {
+        if target.starts_with('/') {
+            return Some(RequestTarget::Origin(target.to_string()));
+        }
+        if target == "*" && *method == HttpMethod::Options {
+            return Some(RequestTarget::Asterisk);
+        }
+
+        let (scheme, rest) = target.split_once("://")?;
+        if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+            return None;
+        }
+        let path_start = rest.find(['/', '?']).unwrap_or(rest.len());
+        if path_start == 0 {
+            return None;
+        }
+        let path_and_query = match &rest[path_start..] {
+            path if path.starts_with('/') => path.to_string(),
+            query => format!("/{}", query),
+        };
+
+        Some(RequestTarget::Absolute(Uri {
+            scheme: scheme.to_ascii_lowercase(),
+            authority: rest[..path_start].to_string(),
+            path_and_query,
+        }))
+    }
+
+    /// The path and query the target asks for, still percent-encoded, or `*`.
+    pub fn path_and_query(&self) -> &str {
+        match self {
+            RequestTarget::Origin(target) => target,
+            RequestTarget::Asterisk => "*",
+            RequestTarget::Absolute(uri) => &uri.path_and_query,
         }
     }
 }
 
-pub enum HttpStatusCode {
-    Ok = 200,
-    Created = 201,
-    NotFound = 404,
+/// Returns the offset just past the blank line separating the headers from the body.
+/// Lines may end in `\r\n` or, as hand-typed requests often do, a bare `\n`.
+pub fn find_header_end(buf: &[u8]) -> Option<usize> {
+    let mut line_start = 0;
+
+    for (i, &byte) in buf.iter().enumerate() {
+        if byte != b'\n' {
+            continue;
+        }
+        let line = &buf[line_start..i];
+        if line_start > 0 && (line.is_empty() || line == b"\r") {
+            return Some(i + 1);
+        }
+        line_start = i + 1;
+    }
+
+    None
 }
 
-impl fmt::Display for HttpStatusCode {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.canonical_reason())
+/// Returns whether a `Transfer-Encoding` header value ends with the `chunked` coding.
+pub fn is_chunked(transfer_encoding: &str) -> bool {
+    transfer_encoding
+        .rsplit(',')
+        .next()
+        .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+}
+
+/// Reassembles a `Transfer-Encoding: chunked` body, dropping chunk extensions and
+/// trailers.
+pub fn decode_chunked(body: &[u8]) -> Result<Vec<u8>, ParseError> {
+    let mut decoded = Vec::new();
+    match parse_chunks(body, |chunk| decoded.extend_from_slice(chunk))? {
+        Some(_) => Ok(decoded),
+        None => Err(ParseError::IncompleteChunkedBody),
     }
 }
 
-impl HttpStatusCode {
-    fn canonical_reason(&self) -> &str {
-        match self {
-            HttpStatusCode::Ok => "200 OK",
-            HttpStatusCode::Created => "201 Created",
-            HttpStatusCode::NotFound => "404 Not Found",
+/// Returns how many bytes of `body` the chunked encoding spans, up to and including
+/// the blank line after the final chunk, or `None` if it hasn't all arrived yet.
+pub fn chunked_length(body: &[u8]) -> Result<Option<usize>, ParseError> {
+    parse_chunks(body, |_| {})
+}
+
+fn parse_chunks(body: &[u8], mut on_chunk: impl FnMut(&[u8])) -> Result<Option<usize>, ParseError> {
+    let line_end = |from: usize| {
+        body[from..]
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .map(|pos| from + pos)
+    };
+
+    let mut pos = 0;
+    loop {
+        let Some(end) = line_end(pos) else {
+            return Ok(None);
+        };
+        let size_line =
+            std::str::from_utf8(&body[pos..end]).map_err(|_| ParseError::InvalidChunkSize)?;
+        let size = size_line.split(';').next().unwrap_or("").trim();
+        if size.is_empty() || !size.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(ParseError::InvalidChunkSize);
+        }
+        let size = usize::from_str_radix(size, 16).map_err(|_| ParseError::InvalidChunkSize)?;
+        pos = end + 2;
+
+        if size == 0 {
+            break;
+        }
+
+        let Some(data_end) = pos
+            .checked_add(size)
+            .filter(|&data_end| data_end + 2 <= body.len())
+        else {
+            return Ok(None);
+        };
+        if &body[data_end..data_end + 2] != b"\r\n" {
+            return Err(ParseError::InvalidChunkSize);
+        }
+        on_chunk(&body[pos..data_end]);
+        pos = data_end + 2;
+    }
+
+    // Skip any trailer fields up to the blank line ending the body.
+    loop {
+        let Some(end) = line_end(pos) else {
+            return Ok(None);
+        };
+        let empty = end == pos;
+        pos = end + 2;
+        if empty {
+            return Ok(Some(pos));
         }
     }
 }
 
-pub struct HttpResponse {
-    pub status_code: HttpStatusCode,
-    pub content: Option<HttpContent>,
+/// Decodes the `%XX` escapes in a path. Escaped slashes (`%2F`) decode to `/` like
+/// any other byte, while incomplete (`%2`) or invalid (`%zz`) escapes are kept as-is.
+pub fn percent_decode(input: &str) -> Cow<'_, str> {
+    decode(input, false)
 }
 
-impl fmt::Display for HttpResponse {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &self.content {
-            Some(content) => {
-                write!(
-                    f,
-                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
-                    self.status_code,
-                    content.content_type,
-                    content.content.len(),
-                    content.content
-                )
-            }
-            None => {
-                write!(f, "HTTP/1.1 {}\r\n\r\n", self.status_code)
+/// Decodes `a=1&b=hello+world`, the format of both query strings and form bodies, into
+/// each key's values in the order they were sent. Keys and values are percent-decoded
+/// with `+` meaning a space, and a key without `=` gets an empty value.
+pub fn parse_form_urlencoded(body: &str) -> QueryParams {
+    let mut params = QueryParams::new();
+
+    for pair in body.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params
+            .entry(decode_query_component(key).into_owned())
+            .or_default()
+            .push(decode_query_component(value).into_owned());
+    }
+
+    params
+}
+
+/// Like [`percent_decode`], but also decodes `+` as a space as form-encoded query
+/// strings require.
+fn decode_query_component(input: &str) -> Cow<'_, str> {
+    decode(input, true)
+}
+
+fn decode(input: &str, plus_as_space: bool) -> Cow<'_, str> {
+    if !(input.contains('%') || plus_as_space && input.contains('+')) {
+        return Cow::Borrowed(input);
+    }
+
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
             }
+            (b'+', _) if plus_as_space => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
         }
+        i += 1;
     }
+
+    Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
 }
 
-#[derive(Debug, PartialEq)]
-pub struct HttpContent {
-    pub content: String,
-    pub content_type: HttpContentType,
+/// Header map keyed by lowercased name, so lookups ignore case. The name as it was
+/// first sent is kept alongside the values so it shows up when debugging. A header
+/// sent more than once keeps every value, in order.
+#[derive(Debug, Default, PartialEq)]
+pub struct HttpHeaders {
+    entries: HashMap<String, (String, Vec<String>)>,
+}
+
+impl HttpHeaders {
+    /// Adds a value for `name`, after any it already has.
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.entries
+            .entry(name.to_ascii_lowercase())
+            .or_insert_with(|| (name.to_string(), Vec::new()))
+            .1
+            .push(value.to_string());
+    }
+
+    /// Returns the first value of `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_all(name).first().map(|value| value.as_str())
+    }
+
+    /// Returns every value of `name`, in the order they were sent.
+    pub fn get_all(&self, name: &str) -> &[String] {
+        self.entries
+            .get(&name.to_ascii_lowercase())
+            .map_or(&[], |(_, values)| values.as_slice())
+    }
+}
+
+impl FromIterator<(String, String)> for HttpHeaders {
+    fn from_iter<T: IntoIterator<Item = (String, String)>>(iter: T) -> Self {
+        let mut headers = HttpHeaders::default();
+        for (name, value) in iter {
+            headers.insert(&name, &value);
+        }
+        headers
+    }
 }
 
 #[derive(Debug, PartialEq)]
-pub enum HttpContentType {
-    ApplicationJson,
-    ApplicationOctetStream,
-    TextPlain,
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Delete,
+    Patch,
+    Head,
+    Options,
 }
 
-impl HttpContentType {
+impl HttpMethod {
     fn as_str(&self) -> &'static str {
         match self {
-            HttpContentType::ApplicationJson => "application/json",
-            HttpContentType::ApplicationOctetStream => "application/octet-stream",
-            HttpContentType::TextPlain => "text/plain",
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Options => "OPTIONS",
         }
     }
+
+    /// Whether the method only reads, so a request with it changes nothing on the
+    /// server (RFC 9110, section 9.2.1).
+    pub fn is_safe(&self) -> bool {
+        matches!(
+            self,
+            HttpMethod::Get | HttpMethod::Head | HttpMethod::Options
+        )
+    }
+
+    /// Whether sending a request with the method several times has the same effect as
+    /// sending it once, so it can be retried (RFC 9110, section 9.2.2).
+    pub fn is_idempotent(&self) -> bool {
+        self.is_safe() || matches!(self, HttpMethod::Put | HttpMethod::Delete)
+    }
 }
 
-impl fmt::Display for HttpContentType {
+impl fmt::Display for HttpMethod {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.as_str())
     }
 }
 
-impl FromStr for HttpContentType {
+impl FromStr for HttpMethod {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "application/json" => Ok(HttpContentType::ApplicationJson),
-            "application/octet-stream" => Ok(HttpContentType::ApplicationOctetStream),
-            "text/plain" => Ok(HttpContentType::TextPlain),
+            "GET" => Ok(HttpMethod::Get),
+            "POST" => Ok(HttpMethod::Post),
+            "PUT" => Ok(HttpMethod::Put),
+            "DELETE" => Ok(HttpMethod::Delete),
+            "PATCH" => Ok(HttpMethod::Patch),
+            "HEAD" => Ok(HttpMethod::Head),
+            "OPTIONS" => Ok(HttpMethod::Options),
             _ => Err(()),
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+#[derive(Debug, PartialEq)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+}
 
-    #[test]
-    fn test_parse_simple_get_request() {
-        let raw_request = "GET /example/resource HTTP/1.1\r\n\r\n";
-        let request = HttpRequest::new(raw_request);
+impl HttpVersion {
+    fn as_str(&self) -> &'static str {
+        match self {
+            HttpVersion::Http10 => "HTTP/1.0",
+            HttpVersion::Http11 => "HTTP/1.1",
+        }
+    }
+}
 
-        let expected = HttpRequest {
-            method: HttpMethod::Get,
-            path: String::from("/example/resource"),
-            headers: HashMap::new(),
-            content: None,
-        };
-        assert_eq!(request, expected);
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
     }
+}
 
-    #[test]
-    fn test_parse_simple_get_request_with_headers() {
-        let raw_request = "GET /example/resource HTTP/1.1\r\nHost: www.example.com\r\nUser-Agent: Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:100.0) Gecko/20100101 Firefox/100.0\r\n\r\n";
-        let request = HttpRequest::new(raw_request);
+impl FromStr for HttpVersion {
+    type Err = ();
 
-        let headers = [
-            (
-                String::from("Host"), 
-                String::from("www.example.com")
-            ),
-            (
-            String::from("User-Agent"),
-            String::from(
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:100.0) Gecko/20100101 Firefox/100.0",
-            ),
-        )];
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HTTP/1.0" => Ok(HttpVersion::Http10),
+            "HTTP/1.1" => Ok(HttpVersion::Http11),
+            _ => Err(()),
+        }
+    }
+}
 
-        let expected = HttpRequest {
-            method: HttpMethod::Get,
-            path: String::from("/example/resource"),
-            headers: HashMap::from(headers),
-            content: None,
-        };
-        assert_eq!(request, expected);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpStatusCode {
+    Continue = 100,
+    Ok = 200,
+    Created = 201,
+    NoContent = 204,
+    PartialContent = 206,
+    MovedPermanently = 301,
+    Found = 302,
+    NotModified = 304,
+    BadRequest = 400,
+    Unauthorized = 401,
+    Forbidden = 403,
+    NotFound = 404,
+    MethodNotAllowed = 405,
+    RequestTimeout = 408,
+    Conflict = 409,
+    PayloadTooLarge = 413,
+    RangeNotSatisfiable = 416,
+    TooManyRequests = 429,
+    RequestHeaderFieldsTooLarge = 431,
+    InternalServerError = 500,
+    NotImplemented = 501,
+    ServiceUnavailable = 503,
+    HttpVersionNotSupported = 505,
+}
+
+impl fmt::Display for HttpStatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.reason())
     }
+}
 
-    #[test]
-    fn test_parse_simple_post_request() {
-        let raw_request = "POST /example/resource HTTP/1.1\r\nHost: www.example.com\r\nContent-Type: application/json\r\n\r\n{\"key1\": \"value1\", \"key2\": \"value2\"}";
-        let request = HttpRequest::new(raw_request);
+impl HttpStatusCode {
+    /// The status code with the numeric value `code`, if it's one this server knows.
+    pub fn from_u16(code: u16) -> Option<HttpStatusCode> {
+        match code {
+            100 => Some(HttpStatusCode::Continue),
+            200 => Some(HttpStatusCode::Ok),
+            201 => Some(HttpStatusCode::Created),
+            204 => Some(HttpStatusCode::NoContent),
+            206 => Some(HttpStatusCode::PartialContent),
+            301 => Some(HttpStatusCode::MovedPermanently),
+            302 => Some(HttpStatusCode::Found),
+            304 => Some(HttpStatusCode::NotModified),
+            400 => Some(HttpStatusCode::BadRequest),
+            401 => Some(HttpStatusCode::Unauthorized),
+            403 => Some(HttpStatusCode::Forbidden),
+            404 => Some(HttpStatusCode::NotFound),
+            405 => Some(HttpStatusCode::MethodNotAllowed),
+            408 => Some(HttpStatusCode::RequestTimeout),
+            409 => Some(HttpStatusCode::Conflict),
+            413 => Some(HttpStatusCode::PayloadTooLarge),
+            416 => Some(HttpStatusCode::RangeNotSatisfiable),
+            429 => Some(HttpStatusCode::TooManyRequests),
+            431 => Some(HttpStatusCode::RequestHeaderFieldsTooLarge),
+            500 => Some(HttpStatusCode::InternalServerError),
+            501 => Some(HttpStatusCode::NotImplemented),
+            503 => Some(HttpStatusCode::ServiceUnavailable),
+            505 => Some(HttpStatusCode::HttpVersionNotSupported),
+            _ => None,
+        }
+    }
 
-        let headers = [
+    /// The numeric code, e.g. `200`.
+    pub fn code(&self) -> u16 {
+        *self as u16
+    }
+
+    /// The reason phrase sent after the code in the status line, e.g. `OK`.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            HttpStatusCode::Continue => "Continue",
+            HttpStatusCode::Ok => "OK",
+            HttpStatusCode::Created => "Created",
+            HttpStatusCode::NoContent => "No Content",
+            HttpStatusCode::PartialContent => "Partial Content",
+            HttpStatusCode::MovedPermanently => "Moved Permanently",
+            HttpStatusCode::Found => "Found",
+            HttpStatusCode::NotModified => "Not Modified",
+            HttpStatusCode::BadRequest => "Bad Request",
+            HttpStatusCode::Unauthorized => "Unauthorized",
+            HttpStatusCode::Forbidden => "Forbidden",
+            HttpStatusCode::NotFound => "Not Found",
+            HttpStatusCode::MethodNotAllowed => "Method Not Allowed",
+            HttpStatusCode::RequestTimeout => "Request Timeout",
+            HttpStatusCode::Conflict => "Conflict",
+            HttpStatusCode::PayloadTooLarge => "Payload Too Large",
+            HttpStatusCode::RangeNotSatisfiable => "Range Not Satisfiable",
+            HttpStatusCode::TooManyRequests => "Too Many Requests",
+            HttpStatusCode::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
+            HttpStatusCode::InternalServerError => "Internal Server Error",
+            HttpStatusCode::NotImplemented => "Not Implemented",
+            HttpStatusCode::ServiceUnavailable => "Service Unavailable",
+            HttpStatusCode::HttpVersionNotSupported => "HTTP Version Not Supported",
+        }
+    }
+}
+
+pub struct HttpResponse {
+    pub status_code: HttpStatusCode,
+    pub content: Option<HttpContent<BodySource>>,
+    /// Extra headers sent after the status line.
+    pub headers: HashMap<String, String>,
+    /// Whether the connection stays open after this response is sent.
+    pub keep_alive: bool,
+    /// When the response was generated, sent as the `Date` header.
+    pub date: SystemTime,
+    /// Sent as the `Server` header, or left out when `None`.
+    pub server: Option<String>,
+    /// Whether this answers a HEAD request, so the headers describe the body but it
+    /// isn't sent.
+    pub head: bool,
+    /// Whether [`HttpResponse::compress`] may compress the body, which its content
+    /// type can still rule out.
+    pub compressible: bool,
+}
+
+/// The default `Server` header value identifying this software.
+pub const SERVER: &str = concat!("rust-http-server/", env!("CARGO_PKG_VERSION"));
+
+/// Headers the serializer always writes itself, so entries for them in
+/// [`HttpResponse::headers`] are skipped rather than sent twice.
+const GENERATED_HEADERS: [&str; 5] = [
+    "Connection",
+    "Content-Length",
+    "Date",
+    "Server",
+    "Transfer-Encoding",
+];
+
+/// How much of a [`BodySource::Stream`] body is read to fill each chunk sent.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+impl HttpResponse {
+    pub fn new(status_code: HttpStatusCode, content: Option<HttpContent>) -> HttpResponse {
+        HttpResponse {
+            status_code,
+            content: content.map(|content| HttpContent {
+                content: BodySource::Bytes(content.content),
+                content_type: content.content_type,
+            }),
+            headers: HashMap::new(),
+            keep_alive: false,
+            date: SystemTime::now(),
+            server: Some(SERVER.to_string()),
+            head: false,
+            compressible: true,
+        }
+    }
+
+    /// Builds a response whose body is the next `len` bytes of `file`, copied to the
+    /// connection as it's sent.
+    pub fn file(
+        status_code: HttpStatusCode,
+        file: File,
+        len: u64,
+        content_type: HttpContentType,
+    ) -> HttpResponse {
+        HttpResponse {
+            content: Some(HttpContent {
+                content: BodySource::File(file, len),
+                content_type,
+            }),
+            ..HttpResponse::new(status_code, None)
+        }
+    }
+
+    /// Builds a response whose body is read from `reader` until it ends, sent with
+    /// `Transfer-Encoding: chunked` since its length isn't known up front.
+    pub fn stream(
+        status_code: HttpStatusCode,
+        reader: impl Read + Send + 'static,
+        content_type: HttpContentType,
+    ) -> HttpResponse {
+        HttpResponse {
+            content: Some(HttpContent {
+                content: BodySource::Stream(Box::new(reader)),
+                content_type,
+            }),
+            ..HttpResponse::new(status_code, None)
+        }
+    }
+
+    /// Builds a redirect to `location`. Panics if `status_code` isn't a 3xx status.
+    pub fn redirect(status_code: HttpStatusCode, location: &str) -> HttpResponse {
+        assert!(
+            (300..400).contains(&status_code.code()),
+            "{} is not a redirect status",
+            status_code
+        );
+        HttpResponse::new(status_code, None).with_header("Location", location)
+    }
+
+    /// Builds a response without a body.
+    pub fn empty(status_code: HttpStatusCode) -> HttpResponse {
+        HttpResponse::new(status_code, None)
+    }
+
+    /// Builds a response whose body is `text`, as `text/plain`.
+    pub fn text(status_code: HttpStatusCode, text: &str) -> HttpResponse {
+        HttpResponse::new(
+            status_code,
+            Some(HttpContent {
+                content: text.as_bytes().to_vec(),
+                content_type: HttpContentType::TextPlain,
+            }),
+        )
+    }
+
+    /// Builds a response whose body is the page `html`, as `text/html`.
+    pub fn html(status_code: HttpStatusCode, html: &str) -> HttpResponse {
+        HttpResponse::new(
+            status_code,
+            Some(HttpContent {
+                content: html.as_bytes().to_vec(),
+                content_type: HttpContentType::TextHtml,
+            }),
+        )
+    }
+
+    /// Builds a `503 Service Unavailable` asking the client to try again after
+    /// `retry_after`, rounded down to whole seconds.
+    pub fn service_unavailable(retry_after: Duration) -> HttpResponse {
+        HttpResponse::empty(HttpStatusCode::ServiceUnavailable)
+            .with_header("Retry-After", &retry_after.as_secs().to_string())
+    }
+
+    /// Builds a response whose body is `value` serialized as `application/json`.
+    pub fn json(status_code: HttpStatusCode, value: &JsonValue) -> HttpResponse {
+        HttpResponse::new(
+            status_code,
+            Some(HttpContent {
+                content: value.to_string().into_bytes(),
+                content_type: HttpContentType::ApplicationJson,
+            }),
+        )
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds `header` to the `Vary` header, keeping any already listed there, to tell
+    /// caches the response depends on that request header.
+    pub fn with_vary(mut self, header: &str) -> Self {
+        let key = self
+            .headers
+            .keys()
+            .find(|name| name.eq_ignore_ascii_case("Vary"))
+            .cloned()
+            .unwrap_or_else(|| String::from("Vary"));
+        let vary = self.headers.entry(key).or_default();
+        let listed = vary
+            .split(',')
+            .any(|name| name.trim().eq_ignore_ascii_case(header));
+        if !listed {
+            if !vary.is_empty() {
+                vary.push_str(", ");
+            }
+            vary.push_str(header);
+        }
+        self
+    }
+
+    /// Looks up a header set on the response, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Keeps [`HttpResponse::compress`] from compressing the body, for a handler that
+    /// knows it won't get any smaller.
+    pub fn without_compression(mut self) -> Self {
+        self.compressible = false;
+        self
+    }
+
+    /// Compresses the body with the encoding the client's `Accept-Encoding` prefers,
+    /// if there is one and the body is at least `min_size` bytes; smaller bodies
+    /// aren't worth the overhead.
+    ///
+    /// Partial content is left alone, since its `Content-Range` counts uncompressed bytes,
+    /// and so are bodies opted out with [`HttpResponse::without_compression`], already
+    /// encoded, or of a type that doesn't compress.
+    ///
+    /// A body big enough to compress gets `Vary: Accept-Encoding` whether or not this
+    /// client took it compressed, so caches don't hand one variant to the other.
+    pub fn compress(mut self, accept_encoding: Option<&str>, min_size: usize) -> Self {
+        let compressible = self.compressible
+            && self.header("Content-Range").is_none()
+            && self.header("Content-Encoding").is_none()
+            && matches!(
+                self.content.as_ref(),
+                Some(HttpContent { content: BodySource::Bytes(body), content_type })
+                    if body.len() >= min_size && content_type.is_compressible()
+            );
+        if !compressible {
+            return self;
+        }
+        self = self.with_vary("Accept-Encoding");
+
+        let encoding = accept_encoding.and_then(compression::select_encoding);
+        if let Some((BodySource::Bytes(body), encoding)) = self
+            .content
+            .as_mut()
+            .map(|content| &mut content.content)
+            .zip(encoding)
+        {
+            *body = encoding.encode(body);
+            self.headers.insert(
+                String::from("Content-Encoding"),
+                String::from(encoding.as_str()),
+            );
+        }
+        self
+    }
+
+    fn connection(&self) -> &'static str {
+        if self.keep_alive {
+            "keep-alive"
+        } else {
+            "close"
+        }
+    }
+}
+
+impl HttpResponse {
+    /// Serializes the status line, headers and body into the bytes sent on the wire.
+    /// `204 No Content` and `304 Not Modified` responses are sent without a body or
+    /// `Content-Length`, whatever their content, and a response to HEAD without a body.
+    /// Any other response without content says so with `Content-Length: 0`, so
+    /// keep-alive clients know not to wait for one.
+    ///
+    /// A [`BodySource::File`] or [`BodySource::Stream`] body is described in the
+    /// headers but left out; use [`HttpResponse::write_to`] to send it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.head_bytes();
+        if let Some(BodySource::Bytes(body)) = self.body_to_send() {
+            bytes.extend_from_slice(body);
+        }
+        bytes
+    }
+
+    /// Writes the response to `out`: the head and an in-memory body in one write, a
+    /// file body copied across a buffer at a time rather than loaded into memory, and
+    /// a stream as a chunk per read. Returns how many bytes of body were sent, not
+    /// counting the chunked framing.
+    pub fn write_to(mut self, out: &mut impl Write) -> io::Result<u64> {
+        out.write_all(&self.to_bytes())?;
+
+        let sends_body = self.body_to_send().is_some();
+        let sent = match self
+            .content
+            .take()
+            .map(|content| content.content)
+            .filter(|_| sends_body)
+        {
+            Some(BodySource::Bytes(body)) => body.len() as u64,
+            Some(BodySource::File(file, len)) => {
+                let copied = io::copy(&mut file.take(len), out)?;
+                if copied < len {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        format!("file ended after {} of {} bytes", copied, len),
+                    ));
+                }
+                copied
+            }
+            Some(BodySource::Stream(reader)) => write_chunked(reader, out)?,
+            None => 0,
+        };
+        out.flush()?;
+        Ok(sent)
+    }
+
+    /// How many bytes of body are sent on the wire: none for HEAD requests or bodiless
+    /// statuses, and `None` for a stream whose length is only known once it's sent.
+    pub fn body_len(&self) -> Option<u64> {
+        self.body_to_send().map_or(Some(0), BodySource::len)
+    }
+
+    fn bodiless(&self) -> bool {
+        matches!(
+            self.status_code,
+            HttpStatusCode::NoContent | HttpStatusCode::NotModified
+        )
+    }
+
+    /// The content described by the headers, if the status allows one.
+    fn content_to_describe(&self) -> Option<&HttpContent<BodySource>> {
+        self.content.as_ref().filter(|_| !self.bodiless())
+    }
+
+    fn body_to_send(&self) -> Option<&BodySource> {
+        self.content_to_describe()
+            .filter(|_| !self.head)
+            .map(|content| &content.content)
+    }
+
+    fn head_bytes(&self) -> Vec<u8> {
+        let body = self.content_to_describe();
+
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\n",
+            self.status_code.code(),
+            self.status_code.reason()
+        );
+        head.push_str(&format!("Date: {}\r\n", format_http_date(self.date)));
+        if let Some(server) = &self.server {
+            head.push_str(&format!("Server: {}\r\n", server));
+        }
+        head.push_str(&format!("Connection: {}\r\n", self.connection()));
+
+        for (name, value) in self.headers.iter().sorted() {
+            let generated = GENERATED_HEADERS
+                .iter()
+                .any(|header| header.eq_ignore_ascii_case(name));
+            // A Content-Type set by hand takes the place of the body's own.
+            let content_type = body.is_some() && name.eq_ignore_ascii_case("Content-Type");
+
+            if !generated && !content_type {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+
+        if let Some(content) = body {
+            let content_type = match self.header("Content-Type") {
+                Some(content_type) => content_type.to_string(),
+                None => content.content_type.to_string(),
+            };
+            head.push_str(&format!("Content-Type: {}\r\n", content_type));
+            // Content-Length counts the bytes of the body as sent, never characters: "é"
+            // is one character but two bytes of UTF-8.
+            match content.content.len() {
+                Some(len) => head.push_str(&format!("Content-Length: {}\r\n", len)),
+                None => head.push_str("Transfer-Encoding: chunked\r\n"),
+            }
+        } else if !self.bodiless() {
+            head.push_str("Content-Length: 0\r\n");
+        }
+        head.push_str("\r\n");
+
+        head.into_bytes()
+    }
+}
+
+/// A body and its media type. Requests always carry their body in memory; responses
+/// may also send theirs from a file, see [`BodySource`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpContent<T = Vec<u8>> {
+    pub content: T,
+    pub content_type: HttpContentType,
+}
+
+/// Copies `reader` to `out` with the chunked transfer coding, returning how many bytes
+/// of it were sent.
+fn write_chunked(mut reader: impl Read, out: &mut impl Write) -> io::Result<u64> {
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut sent = 0;
+
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        write!(out, "{:x}\r\n", n)?;
+        out.write_all(&buf[..n])?;
+        out.write_all(b"\r\n")?;
+        sent += n as u64;
+    }
+
+    out.write_all(b"0\r\n\r\n")?;
+    Ok(sent)
+}
+
+/// Where a response body comes from.
+pub enum BodySource {
+    Bytes(Vec<u8>),
+    /// The next `u64` bytes of the file from its current position, read as the
+    /// response is sent so large files never have to fit in memory.
+    File(File, u64),
+    /// A body of unknown length, read until it ends as the response is sent.
+    Stream(Box<dyn Read + Send>),
+}
+
+impl BodySource {
+    /// The number of bytes sent as the body, if it's known before sending.
+    pub fn len(&self) -> Option<u64> {
+        match self {
+            BodySource::Bytes(bytes) => Some(bytes.len() as u64),
+            BodySource::File(_, len) => Some(*len),
+            BodySource::Stream(_) => None,
+        }
+    }
+
+    /// Whether the body is known to be empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// The body, if it's held in memory.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            BodySource::Bytes(bytes) => Some(bytes),
+            BodySource::File(..) | BodySource::Stream(_) => None,
+        }
+    }
+}
+
+impl fmt::Debug for BodySource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BodySource::Bytes(bytes) => f.debug_tuple("Bytes").field(bytes).finish(),
+            BodySource::File(file, len) => f.debug_tuple("File").field(file).field(len).finish(),
+            BodySource::Stream(_) => f.write_str("Stream(..)"),
+        }
+    }
+}
+
+impl From<Vec<u8>> for BodySource {
+    fn from(bytes: Vec<u8>) -> Self {
+        BodySource::Bytes(bytes)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HttpContentType {
+    ApplicationFormUrlencoded,
+    ApplicationJavascript,
+    ApplicationJson,
+    ApplicationOctetStream,
+    ImageGif,
+    ImageJpeg,
+    ImagePng,
+    ImageSvg,
+    MultipartFormData,
+    TextCss,
+    TextHtml,
+    TextPlain,
+    /// Any other media type, kept as it was given so it round-trips.
+    Other(String),
+}
+
+impl HttpContentType {
+    fn as_str(&self) -> &str {
+        match self {
+            HttpContentType::ApplicationFormUrlencoded => "application/x-www-form-urlencoded",
+            HttpContentType::ApplicationJavascript => "application/javascript",
+            HttpContentType::ApplicationJson => "application/json",
+            HttpContentType::ApplicationOctetStream => "application/octet-stream",
+            HttpContentType::ImageGif => "image/gif",
+            HttpContentType::ImageJpeg => "image/jpeg",
+            HttpContentType::ImagePng => "image/png",
+            HttpContentType::ImageSvg => "image/svg+xml",
+            HttpContentType::MultipartFormData => "multipart/form-data",
+            HttpContentType::TextCss => "text/css",
+            HttpContentType::TextHtml => "text/html",
+            HttpContentType::TextPlain => "text/plain",
+            HttpContentType::Other(content_type) => content_type,
+        }
+    }
+}
+
+impl HttpContentType {
+    /// Whether a body of this type is worth compressing. Most image, audio and video
+    /// formats and archives are compressed already, and opaque binary data may be too.
+    pub fn is_compressible(&self) -> bool {
+        match self {
+            HttpContentType::ApplicationOctetStream
+            | HttpContentType::ImageGif
+            | HttpContentType::ImageJpeg
+            | HttpContentType::ImagePng => false,
+            HttpContentType::Other(content_type) => {
+                let content_type = content_type.to_ascii_lowercase();
+                let (main_type, subtype) = content_type.split_once('/').unwrap_or_default();
+                !matches!(main_type, "image" | "audio" | "video")
+                    && !matches!(
+                        subtype,
+                        "zip" | "gzip" | "x-gzip" | "x-bzip2" | "x-xz" | "zstd" | "x-7z-compressed"
+                    )
+            }
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for HttpContentType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for HttpContentType {
+    type Err = ();
+
+    /// Media types are matched case-insensitively; anything unrecognised becomes
+    /// [`HttpContentType::Other`], so parsing never fails.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "application/x-www-form-urlencoded" => Ok(HttpContentType::ApplicationFormUrlencoded),
+            "application/javascript" => Ok(HttpContentType::ApplicationJavascript),
+            "application/json" => Ok(HttpContentType::ApplicationJson),
+            "application/octet-stream" => Ok(HttpContentType::ApplicationOctetStream),
+            "image/gif" => Ok(HttpContentType::ImageGif),
+            "image/jpeg" => Ok(HttpContentType::ImageJpeg),
+            "image/png" => Ok(HttpContentType::ImagePng),
+            "image/svg+xml" => Ok(HttpContentType::ImageSvg),
+            "multipart/form-data" => Ok(HttpContentType::MultipartFormData),
+            "text/css" => Ok(HttpContentType::TextCss),
+            "text/html" => Ok(HttpContentType::TextHtml),
+            "text/plain" => Ok(HttpContentType::TextPlain),
+            _ => Ok(HttpContentType::Other(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn test_parse_simple_get_request() {
+        let raw_request = "GET /example/resource HTTP/1.1\r\n\r\n";
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+
+        let expected = HttpRequest {
+            method: HttpMethod::Get,
+            target: RequestTarget::Origin(String::from("/example/resource")),
+            path: String::from("/example/resource"),
+            query: HashMap::new(),
+            version: HttpVersion::Http11,
+            headers: HttpHeaders::default(),
+            content: None,
+            remote_addr: None,
+        };
+        assert_eq!(request, expected);
+    }
+
+    #[test]
+    fn test_parse_simple_get_request_with_headers() {
+        let raw_request = "GET /example/resource HTTP/1.1\r\nHost: www.example.com\r\nUser-Agent: Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:100.0) Gecko/20100101 Firefox/100.0\r\n\r\n";
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+
+        let headers = [
+            (
+                String::from("Host"), 
+                String::from("www.example.com")
+            ),
+            (
+            String::from("User-Agent"),
+            String::from(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:100.0) Gecko/20100101 Firefox/100.0",
+            ),
+        )];
+
+        let expected = HttpRequest {
+            method: HttpMethod::Get,
+            target: RequestTarget::Origin(String::from("/example/resource")),
+            path: String::from("/example/resource"),
+            query: HashMap::new(),
+            version: HttpVersion::Http11,
+            headers: HttpHeaders::from_iter(headers),
+            content: None,
+            remote_addr: None,
+        };
+        assert_eq!(request, expected);
+    }
+
+    #[test]
+    fn test_parse_simple_post_request() {
+        let raw_request = "POST /example/resource HTTP/1.1\r\nHost: www.example.com\r\nContent-Type: application/json\r\nContent-Length: 36\r\n\r\n{\"key1\": \"value1\", \"key2\": \"value2\"}";
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+
+        let headers = [
             (String::from("Host"), String::from("www.example.com")),
             (
                 String::from("Content-Type"),
                 String::from("application/json"),
             ),
+            (String::from("Content-Length"), String::from("36")),
         ];
 
         let expected = HttpRequest {
             method: HttpMethod::Post,
+            target: RequestTarget::Origin(String::from("/example/resource")),
             path: String::from("/example/resource"),
-            headers: HashMap::from(headers),
+            query: HashMap::new(),
+            version: HttpVersion::Http11,
+            headers: HttpHeaders::from_iter(headers),
             content: Some(HttpContent {
-                content: String::from("{\"key1\": \"value1\", \"key2\": \"value2\"}"),
+                content: b"{\"key1\": \"value1\", \"key2\": \"value2\"}".to_vec(),
                 content_type: HttpContentType::ApplicationJson,
             }),
+            remote_addr: None,
         };
         assert_eq!(request, expected);
     }
+
+    #[test]
+    fn test_content_length() {
+        let headers = |lines: &[&str]| {
+            let head = format!("POST / HTTP/1.1\r\n{}\r\n\r\n", lines.join("\r\n"));
+            parse_header_block(head.as_bytes(), false).unwrap()
+        };
+
+        assert_eq!(
+            content_length(&headers(&["Content-Length: 5"])),
+            Ok(Some(5))
+        );
+        assert_eq!(content_length(&headers(&["Host: a"])), Ok(None));
+        assert_eq!(
+            content_length(&headers(&["Content-Length: 5", "content-length: 5"])),
+            Ok(Some(5))
+        );
+        assert_eq!(
+            content_length(&headers(&["Content-Length: 5, 5"])),
+            Ok(Some(5))
+        );
+        for lines in [
+            &["Content-Length: five"][..],
+            &["Content-Length: 5", "Content-Length: 6"],
+            &["Content-Length: 5, 6"],
+            &["Content-Length:"],
+        ] {
+            assert_eq!(
+                content_length(&headers(lines)),
+                Err(ParseError::InvalidContentLength)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_post_request_ignores_bytes_past_content_length() {
+        let raw_request =
+            "POST /files/a HTTP/1.1\r\nContent-Length: 7\r\n\r\nab\x00cd\x00\x00\x00\x00\x00";
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+
+        assert_eq!(
+            request.content,
+            Some(HttpContent {
+                content: b"ab\x00cd\x00\x00".to_vec(),
+                content_type: HttpContentType::TextPlain,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_body_against_content_length() {
+        let request =
+            HttpRequest::new(b"POST /files/a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+        assert_eq!(request.content.unwrap().content, b"hello");
+
+        let request = HttpRequest::new(
+            b"POST /files/a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET / HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(request.content.unwrap().content, b"hello");
+
+        let request =
+            HttpRequest::new(b"POST /files/a HTTP/1.1\r\nContent-Length: 100\r\n\r\nhello");
+        assert_eq!(
+            request,
+            Err(ParseError::IncompleteBody {
+                expected: 100,
+                actual: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        let body = b"4\r\nWiki\r\n7;name=value\r\npedia i\r\nB\r\nn \r\nchunks.\r\n0\r\n\r\n";
+        assert_eq!(decode_chunked(body).unwrap(), b"Wikipedia in \r\nchunks.");
+        assert_eq!(chunked_length(body), Ok(Some(body.len())));
+
+        let with_trailer = b"5\r\nhello\r\n0\r\nExpires: never\r\n\r\nGET / HTTP/1.1";
+        assert_eq!(decode_chunked(with_trailer).unwrap(), b"hello");
+        assert_eq!(
+            chunked_length(with_trailer),
+            Ok(Some(with_trailer.len() - 14))
+        );
+
+        assert_eq!(decode_chunked(b"0\r\n\r\n").unwrap(), b"");
+    }
+
+    #[test]
+    fn test_decode_chunked_errors() {
+        assert_eq!(chunked_length(b"5\r\nhel"), Ok(None));
+        assert_eq!(chunked_length(b"5\r\nhello\r\n0\r\n"), Ok(None));
+        assert_eq!(
+            decode_chunked(b"5\r\nhello\r\n"),
+            Err(ParseError::IncompleteChunkedBody)
+        );
+        assert_eq!(
+            decode_chunked(b"zz\r\nhello\r\n0\r\n\r\n"),
+            Err(ParseError::InvalidChunkSize)
+        );
+        assert_eq!(
+            decode_chunked(b"3\r\nhello\r\n0\r\n\r\n"),
+            Err(ParseError::InvalidChunkSize)
+        );
+    }
+
+    #[test]
+    fn test_parse_chunked_request() {
+        let raw_request = b"POST /files/a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let request = HttpRequest::new(raw_request).unwrap();
+        assert_eq!(request.content.unwrap().content, b"hello world");
+    }
+
+    #[test]
+    fn test_body_accessors() {
+        let request =
+            HttpRequest::new(b"POST /echo HTTP/1.1\r\nContent-Length: 6\r\n\r\nh\xc3\xa9llo")
+                .unwrap();
+        assert_eq!(request.body_bytes(), "héllo".as_bytes());
+        assert_eq!(request.body_str(), Some("héllo"));
+
+        let request =
+            HttpRequest::new(b"POST /echo HTTP/1.1\r\nContent-Length: 4\r\n\r\n\x89PN\xff")
+                .unwrap();
+        assert_eq!(request.body_bytes(), b"\x89PN\xff");
+        assert_eq!(request.body_str(), None);
+
+        let request = HttpRequest::new(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.body_bytes(), b"");
+        assert_eq!(request.body_str(), Some(""));
+    }
+
+    #[test]
+    fn test_content_type() {
+        let parse = |content_type: &str| {
+            let raw_request = format!(
+                "POST / HTTP/1.1\r\nContent-Type: {}\r\nContent-Length: 2\r\n\r\nhi",
+                content_type
+            );
+            HttpRequest::new(raw_request.as_bytes()).unwrap()
+        };
+
+        let request = parse("application/json");
+        assert_eq!(
+            request.content_type(),
+            Some((HttpContentType::ApplicationJson, MediaParams::new()))
+        );
+        assert_eq!(
+            request.content.unwrap().content_type,
+            HttpContentType::ApplicationJson
+        );
+
+        let request = parse("Text/Plain; Charset=utf-8");
+        let (content_type, params) = request.content_type().unwrap();
+        assert_eq!(content_type, HttpContentType::TextPlain);
+        assert_eq!(params["charset"], "utf-8");
+        assert_eq!(
+            request.content.unwrap().content_type,
+            HttpContentType::TextPlain
+        );
+
+        let request = parse(r#"multipart/form-data ; boundary="a; \"b\"" ; junk; x = 1"#);
+        let (content_type, params) = request.content_type().unwrap();
+        assert_eq!(content_type, HttpContentType::MultipartFormData);
+        assert_eq!(
+            params,
+            MediaParams::from([
+                (String::from("boundary"), String::from("a; \"b\"")),
+                (String::from("junk"), String::new()),
+                (String::from("x"), String::from("1")),
+            ])
+        );
+
+        let request = HttpRequest::new(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.content_type(), None);
+    }
+
+    #[test]
+    fn test_parse_body_without_content_length() {
+        let request = HttpRequest::new(b"POST /files/a HTTP/1.1\r\n\r\nhello");
+        assert_eq!(request, Err(ParseError::MissingContentLength));
+        let request = HttpRequest::new(b"POST /files/a HTTP/1.1\r\n\r\n\x00\x00\x00");
+        assert_eq!(request, Err(ParseError::MissingContentLength));
+    }
+
+    #[test]
+    fn test_parse_malformed_requests() {
+        assert_eq!(
+            HttpRequest::new(b"\r\n\r\n"),
+            Err(ParseError::MissingStartLine)
+        );
+        assert_eq!(
+            HttpRequest::new(b"GET\r\n\r\n"),
+            Err(ParseError::MissingStartLine)
+        );
+        assert_eq!(
+            HttpRequest::new(b"BREW /pot HTTP/1.1\r\n\r\n"),
+            Err(ParseError::UnknownMethod(String::from("BREW")))
+        );
+        assert_eq!(
+            HttpRequest::new(b"GET /\r\n\r\n"),
+            Err(ParseError::MissingVersion)
+        );
+        assert_eq!(
+            HttpRequest::new(b"GET / HTTP/3.0\r\n\r\n"),
+            Err(ParseError::UnsupportedVersion(String::from("HTTP/3.0")))
+        );
+        assert_eq!(
+            HttpRequest::new(b"GET / HTTP/1.1\r\nHost: example.com\r\n"),
+            Err(ParseError::MissingHeaderSeparator)
+        );
+        assert_eq!(
+            HttpRequest::new(b"GET /a b HTTP/1.1\r\n\r\n"),
+            Err(ParseError::MalformedStartLine)
+        );
+        assert_eq!(
+            HttpRequest::new(b"GET  HTTP/1.1\r\n\r\n"),
+            Err(ParseError::InvalidTarget(String::new()))
+        );
+    }
+
+    #[test]
+    fn test_parse_request_targets() {
+        let parse = |target: &str| {
+            let raw_request = format!("GET {} HTTP/1.1\r\n\r\n", target);
+            HttpRequest::new(raw_request.as_bytes()).map(|request| (request.path, request.query))
+        };
+
+        assert_eq!(
+            parse("http://example.com/a%20b?x=1"),
+            Ok((
+                String::from("/a b"),
+                QueryParams::from([(String::from("x"), vec![String::from("1")])])
+            ))
+        );
+        assert_eq!(
+            parse("HTTPS://example.com:8443"),
+            Ok((String::from("/"), QueryParams::new()))
+        );
+        assert_eq!(
+            parse("http://example.com?x=1").map(|(path, _)| path),
+            Ok(String::from("/"))
+        );
+
+        for target in [
+            "index.html",
+            "example.com/a",
+            "ftp://example.com/a",
+            "http:///a",
+            "*",
+        ] {
+            assert_eq!(
+                parse(target),
+                Err(ParseError::InvalidTarget(target.to_string())),
+                "{} should be rejected",
+                target
+            );
+        }
+
+        let request = HttpRequest::new(b"OPTIONS * HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.target, RequestTarget::Asterisk);
+        assert_eq!(request.path, "*");
+        assert_eq!(request.method, HttpMethod::Options);
+    }
+
+    #[test]
+    fn test_request_target_forms() {
+        let target = |target: &str| {
+            let raw_request = format!("GET {} HTTP/1.1\r\n\r\n", target);
+            HttpRequest::new(raw_request.as_bytes()).unwrap().target
+        };
+
+        assert_eq!(
+            target("/a%20b?x=1"),
+            RequestTarget::Origin(String::from("/a%20b?x=1"))
+        );
+        assert_eq!(
+            target("HTTP://example.com:8080?x=1"),
+            RequestTarget::Absolute(Uri {
+                scheme: String::from("http"),
+                authority: String::from("example.com:8080"),
+                path_and_query: String::from("/?x=1"),
+            })
+        );
+        assert_eq!(target("/a?x=1").path_and_query(), "/a?x=1");
+        assert_eq!(RequestTarget::Asterisk.path_and_query(), "*");
+    }
+
+    #[test]
+    fn test_repeated_headers() {
+        let raw_request =
+            "GET / HTTP/1.1\r\nAccept: text/html\r\nHost: example.com\r\naccept: */*\r\n\r\n";
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+
+        assert_eq!(request.header("Accept"), Some("text/html"));
+        assert_eq!(request.header_all("Accept"), ["text/html", "*/*"]);
+        assert_eq!(request.header_all("Host"), ["example.com"]);
+        assert!(request.header_all("Cookie").is_empty());
+    }
+
+    #[test]
+    fn test_parse_bare_lf_request() {
+        let raw_request = "POST /files/a HTTP/1.1\nHost: localhost\r\nContent-Length: 5\n\nhello";
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+
+        assert_eq!(request.path, "/files/a");
+        assert_eq!(request.header("Host"), Some("localhost"));
+        assert_eq!(request.content.unwrap().content, b"hello");
+
+        assert_eq!(find_header_end(b"GET / HTTP/1.1\n\nrest"), Some(16));
+        assert_eq!(find_header_end(b"GET / HTTP/1.1\r\n\r\nrest"), Some(18));
+        assert_eq!(find_header_end(b"GET / HTTP/1.1\nHost: a\n"), None);
+    }
+
+    #[test]
+    fn test_colon_less_header_line() {
+        let raw_request =
+            "GET / HTTP/1.1\r\nHost: localhost\r\nnot a header\r\nAccept: */*\r\n\r\n";
+
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+        assert_eq!(request.header("Host"), Some("localhost"));
+        assert_eq!(request.header("Accept"), Some("*/*"));
+        assert_eq!(request.header("not a header"), None);
+
+        assert_eq!(
+            HttpRequest::new_strict(raw_request.as_bytes()),
+            Err(ParseError::MalformedHeader(String::from("not a header")))
+        );
+        assert!(HttpRequest::new_strict(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").is_ok());
+    }
+
+    #[test]
+    fn test_folded_header_lines() {
+        let raw_request =
+            "GET / HTTP/1.1\r\nX-Long: first\r\n  second\r\n\tthird\r\nHost: localhost\r\n\r\n";
+        for request in [
+            HttpRequest::new(raw_request.as_bytes()),
+            HttpRequest::new_strict(raw_request.as_bytes()),
+        ] {
+            let request = request.unwrap();
+            assert_eq!(request.header("X-Long"), Some("first second third"));
+            assert_eq!(request.header("Host"), Some("localhost"));
+        }
+
+        // There's nothing for a folded first line to continue.
+        let raw_request = "GET / HTTP/1.1\r\n folded\r\nHost: localhost\r\n\r\n";
+        assert_eq!(
+            HttpRequest::new(raw_request.as_bytes())
+                .unwrap()
+                .header("Host"),
+            Some("localhost")
+        );
+        assert!(matches!(
+            HttpRequest::new_strict(raw_request.as_bytes()),
+            Err(ParseError::MalformedHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_header_lookup_ignores_case() {
+        let raw_request = "GET /user-agent HTTP/1.1\r\nuser-agent: curl/7.64.1\r\n\r\n";
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+
+        assert_eq!(request.header("User-Agent"), Some("curl/7.64.1"));
+        assert_eq!(request.header("USER-AGENT"), Some("curl/7.64.1"));
+        assert_eq!(request.header("Host"), None);
+    }
+
+    fn is_keep_alive(raw_request: &str) -> bool {
+        HttpRequest::new(raw_request.as_bytes())
+            .unwrap()
+            .is_keep_alive()
+    }
+
+    #[test]
+    fn test_keep_alive_http11() {
+        assert!(is_keep_alive("GET / HTTP/1.1\r\n\r\n"));
+        assert!(is_keep_alive(
+            "GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_keep_alive_http11_close() {
+        assert!(!is_keep_alive(
+            "GET / HTTP/1.1\r\nConnection: close\r\n\r\n"
+        ));
+        assert!(!is_keep_alive(
+            "GET / HTTP/1.1\r\nConnection: Upgrade, Close\r\n\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_keep_alive_http10() {
+        assert!(!is_keep_alive("GET / HTTP/1.0\r\n\r\n"));
+        assert!(!is_keep_alive(
+            "GET / HTTP/1.0\r\nConnection: close\r\n\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_keep_alive_http10_keep_alive() {
+        assert!(is_keep_alive(
+            "GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_parse_query_string() {
+        let raw_request = "GET /echo/foo?x=1&y=hello+world&a=1&a=2&q=%26%3D&flag HTTP/1.1\r\n\r\n";
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+
+        assert_eq!(request.path, "/echo/foo");
+        assert_eq!(request.query_param("x"), Some("1"));
+        assert_eq!(request.query_param("y"), Some("hello world"));
+        assert_eq!(request.query_all("a"), ["1", "2"]);
+        assert_eq!(request.query_param("q"), Some("&="));
+        assert_eq!(request.query_param("flag"), Some(""));
+        assert_eq!(request.query_param("missing"), None);
+        assert!(request.query_all("missing").is_empty());
+    }
+
+    #[test]
+    fn test_parse_form_urlencoded() {
+        let form = parse_form_urlencoded("name=John+Doe&age=30&tag=a&tag=b%26c&flag");
+        assert_eq!(form["name"], ["John Doe"]);
+        assert_eq!(form["age"], ["30"]);
+        assert_eq!(form["tag"], ["a", "b&c"]);
+        assert_eq!(form["flag"], [""]);
+
+        let body = "name=John+Doe&age=30";
+        let raw_request = format!(
+            "POST /form HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+        let form = request.form().unwrap();
+        assert_eq!(form["name"], ["John Doe"]);
+        assert_eq!(form["age"], ["30"]);
+
+        let request =
+            HttpRequest::new(b"POST /form HTTP/1.1\r\nContent-Length: 3\r\n\r\na=1").unwrap();
+        assert_eq!(request.form(), None);
+    }
+
+    #[test]
+    fn test_parse_percent_encoded_path() {
+        let raw_request = "GET /echo/hello%20world+again?msg=hello%20world+again HTTP/1.1\r\n\r\n";
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+
+        assert_eq!(request.path, "/echo/hello world+again");
+        assert_eq!(request.query_param("msg"), Some("hello world again"));
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert!(matches!(percent_decode("/plain/path"), Cow::Borrowed(_)));
+        assert_eq!(percent_decode("/a%20b"), "/a b");
+        assert_eq!(percent_decode("/a%2Fb%2fc"), "/a/b/c");
+        assert_eq!(percent_decode("/caf%C3%A9"), "/café");
+        assert_eq!(percent_decode("/a+b"), "/a+b");
+        assert_eq!(percent_decode("/incomplete%2"), "/incomplete%2");
+        assert_eq!(percent_decode("/incomplete%"), "/incomplete%");
+        assert_eq!(percent_decode("/invalid%zz%+1"), "/invalid%zz%+1");
+        assert_eq!(percent_decode("/%ff"), "/\u{FFFD}");
+    }
+
+    #[test]
+    fn test_decode_query_component() {
+        assert_eq!(decode_query_component("a+b%20c"), "a b c");
+        assert_eq!(decode_query_component("a%2Bb"), "a+b");
+        assert_eq!(decode_query_component("%2"), "%2");
+    }
+
+    #[test]
+    fn test_http_method_round_trip() {
+        let methods = [
+            HttpMethod::Get,
+            HttpMethod::Post,
+            HttpMethod::Put,
+            HttpMethod::Delete,
+            HttpMethod::Patch,
+            HttpMethod::Head,
+            HttpMethod::Options,
+        ];
+
+        for method in methods {
+            assert_eq!(HttpMethod::from_str(&method.to_string()), Ok(method));
+        }
+        assert_eq!(HttpMethod::from_str("get"), Err(()));
+    }
+
+    #[test]
+    fn test_http_method_semantics() {
+        // (method, safe, idempotent)
+        let methods = [
+            (HttpMethod::Get, true, true),
+            (HttpMethod::Head, true, true),
+            (HttpMethod::Options, true, true),
+            (HttpMethod::Put, false, true),
+            (HttpMethod::Delete, false, true),
+            (HttpMethod::Post, false, false),
+            (HttpMethod::Patch, false, false),
+        ];
+
+        for (method, safe, idempotent) in methods {
+            assert_eq!(method.is_safe(), safe, "{}", method);
+            assert_eq!(method.is_idempotent(), idempotent, "{}", method);
+        }
+    }
+
+    #[test]
+    fn test_response_includes_date() {
+        let mut response = HttpResponse::new(HttpStatusCode::Ok, None);
+        response.date = SystemTime::UNIX_EPOCH;
+
+        assert_eq!(
+            String::from_utf8(response.to_bytes()).unwrap(),
+            format!(
+                "HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\nServer: rust-http-server/{}\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn test_response_server_header() {
+        let mut response =
+            HttpResponse::new(HttpStatusCode::Ok, None).with_header("Server", "ignored");
+        response.server = Some(String::from("custom/1.0"));
+        let bytes = String::from_utf8(response.to_bytes()).unwrap();
+        assert!(bytes.contains("\r\nServer: custom/1.0\r\n"));
+        assert!(!bytes.contains("ignored"));
+
+        response.server = None;
+        let bytes = String::from_utf8(response.to_bytes()).unwrap();
+        assert!(!bytes.contains("Server:"));
+    }
+
+    #[test]
+    fn test_response_with_headers() {
+        let mut response = HttpResponse::new(
+            HttpStatusCode::Ok,
+            Some(HttpContent {
+                content: b"hello".to_vec(),
+                content_type: HttpContentType::TextPlain,
+            }),
+        )
+        .with_header("Cache-Control", "no-cache")
+        .with_header("content-length", "100")
+        .with_header("Content-Type", "application/json")
+        .with_header("Location", "/elsewhere");
+        response.date = SystemTime::UNIX_EPOCH;
+        response.server = None;
+
+        assert_eq!(
+            String::from_utf8(response.to_bytes()).unwrap(),
+            "HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\nConnection: close\r\nCache-Control: no-cache\r\nLocation: /elsewhere\r\nContent-Type: application/json\r\nContent-Length: 5\r\n\r\nhello"
+        );
+    }
+
+    #[test]
+    fn test_redirect() {
+        let mut response = HttpResponse::redirect(HttpStatusCode::MovedPermanently, "/new");
+        response.date = SystemTime::UNIX_EPOCH;
+        response.server = None;
+
+        assert_eq!(
+            String::from_utf8(response.to_bytes()).unwrap(),
+            "HTTP/1.1 301 Moved Permanently\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\nConnection: close\r\nLocation: /new\r\nContent-Length: 0\r\n\r\n"
+        );
+
+        let response = HttpResponse::redirect(HttpStatusCode::Found, "https://example.com/");
+        assert_eq!(response.header("Location"), Some("https://example.com/"));
+    }
+
+    #[test]
+    fn test_connection_header() {
+        let mut response =
+            HttpResponse::new(HttpStatusCode::Ok, None).with_header("Connection", "upgrade");
+        let connection = |response: &HttpResponse| {
+            let bytes = String::from_utf8(response.to_bytes()).unwrap();
+            bytes
+                .lines()
+                .filter(|line| line.starts_with("Connection: "))
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(connection(&response), ["Connection: close"]);
+        response.keep_alive = true;
+        assert_eq!(connection(&response), ["Connection: keep-alive"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a redirect status")]
+    fn test_redirect_rejects_other_statuses() {
+        HttpResponse::redirect(HttpStatusCode::Ok, "/new");
+    }
+
+    #[test]
+    fn test_json_response() {
+        let value = JsonValue::Object(vec![
+            (
+                String::from("path"),
+                JsonValue::String(String::from("C:\\\"x\"")),
+            ),
+            (
+                String::from("items"),
+                JsonValue::Array(vec![JsonValue::Number(1.0), JsonValue::Object(Vec::new())]),
+            ),
+        ]);
+        let mut response = HttpResponse::json(HttpStatusCode::Created, &value);
+        response.date = SystemTime::UNIX_EPOCH;
+        response.server = None;
+
+        assert_eq!(
+            String::from_utf8(response.to_bytes()).unwrap(),
+            "HTTP/1.1 201 Created\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: 35\r\n\r\n{\"path\":\"C:\\\\\\\"x\\\"\",\"items\":[1,{}]}"
+        );
+    }
+
+    #[test]
+    fn test_text_html_and_empty_responses() {
+        let serialized = |mut response: HttpResponse| {
+            response.date = SystemTime::UNIX_EPOCH;
+            response.server = None;
+            String::from_utf8(response.to_bytes()).unwrap()
+        };
+        const HEAD: &str = "Date: Thu, 01 Jan 1970 00:00:00 GMT\r\nConnection: close\r\n";
+
+        assert_eq!(
+            serialized(HttpResponse::text(HttpStatusCode::Ok, "h\u{e9}llo")),
+            format!(
+                "HTTP/1.1 200 OK\r\n{}Content-Type: text/plain\r\nContent-Length: 6\r\n\r\nh\u{e9}llo",
+                HEAD
+            )
+        );
+        assert_eq!(
+            serialized(HttpResponse::html(HttpStatusCode::NotFound, "<p>gone</p>")),
+            format!(
+                "HTTP/1.1 404 Not Found\r\n{}Content-Type: text/html\r\nContent-Length: 11\r\n\r\n<p>gone</p>",
+                HEAD
+            )
+        );
+        assert_eq!(
+            serialized(HttpResponse::empty(HttpStatusCode::Ok)),
+            format!("HTTP/1.1 200 OK\r\n{}Content-Length: 0\r\n\r\n", HEAD)
+        );
+        // A 204 can't have a body, so it doesn't say how long one is either.
+        assert_eq!(
+            serialized(HttpResponse::empty(HttpStatusCode::NoContent)),
+            format!("HTTP/1.1 204 No Content\r\n{}\r\n", HEAD)
+        );
+    }
+
+    #[test]
+    fn test_service_unavailable() {
+        let mut response = HttpResponse::service_unavailable(Duration::from_millis(120_500));
+        response.date = SystemTime::UNIX_EPOCH;
+        response.server = None;
+
+        assert_eq!(
+            String::from_utf8(response.to_bytes()).unwrap(),
+            "HTTP/1.1 503 Service Unavailable\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\nConnection: close\r\nRetry-After: 120\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    fn written(response: HttpResponse) -> String {
+        let mut out = Vec::new();
+        response.write_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_body_source_content_length() {
+        let head = |content_length: &str| {
+            format!(
+                "HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\nConnection: close\r\n{}\r\n",
+                content_length
+            )
+        };
+        let response = |content: Option<HttpContent<BodySource>>| HttpResponse {
+            content,
+            date: SystemTime::UNIX_EPOCH,
+            server: None,
+            ..HttpResponse::new(HttpStatusCode::Ok, None)
+        };
+
+        let bytes = response(Some(HttpContent {
+            content: BodySource::from(b"hello".to_vec()),
+            content_type: HttpContentType::TextPlain,
+        }));
+        assert_eq!(bytes.body_len(), Some(5));
+        let expected = head("Content-Type: text/plain\r\nContent-Length: 5\r\n") + "hello";
+        assert_eq!(String::from_utf8(bytes.to_bytes()).unwrap(), expected);
+        assert_eq!(written(bytes), expected);
+
+        let dir = TempDir::new("body");
+        let path = dir.join("body.txt");
+        std::fs::write(&path, "hello world").unwrap();
+        let file = |len| {
+            let mut file = File::open(&path).unwrap();
+            io::Seek::seek(&mut file, io::SeekFrom::Start(6)).unwrap();
+            response(Some(HttpContent {
+                content: BodySource::File(file, len),
+                content_type: HttpContentType::TextPlain,
+            }))
+        };
+        let file_head = head("Content-Type: text/plain\r\nContent-Length: 5\r\n");
+        // The file is only read when the response is written out.
+        assert_eq!(String::from_utf8(file(5).to_bytes()).unwrap(), file_head);
+        assert_eq!(written(file(5)), file_head.clone() + "world");
+
+        let mut head_only = file(5);
+        head_only.head = true;
+        assert_eq!(head_only.body_len(), Some(0));
+        assert_eq!(written(head_only), file_head);
+
+        let e = file(20).write_to(&mut Vec::new()).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+
+        let empty = response(None);
+        assert_eq!(empty.body_len(), Some(0));
+        assert_eq!(written(empty), head("Content-Length: 0\r\n"));
+    }
+
+    #[test]
+    fn test_stream_response_is_chunked() {
+        let data: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+        let response = HttpResponse {
+            date: SystemTime::UNIX_EPOCH,
+            server: None,
+            ..HttpResponse::stream(
+                HttpStatusCode::Ok,
+                io::Cursor::new(data.clone()),
+                HttpContentType::ApplicationOctetStream,
+            )
+        };
+        assert_eq!(response.body_len(), None);
+
+        let mut out = Vec::new();
+        assert_eq!(response.write_to(&mut out).unwrap(), data.len() as u64);
+        let header_end = find_header_end(&out).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&out[..header_end]),
+            "HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\nConnection: close\r\nContent-Type: application/octet-stream\r\nTransfer-Encoding: chunked\r\n\r\n"
+        );
+        assert!(out.ends_with(b"\r\n0\r\n\r\n"));
+        assert_eq!(decode_chunked(&out[header_end..]).unwrap(), data);
+
+        let mut empty = Vec::new();
+        HttpResponse::stream(HttpStatusCode::Ok, io::empty(), HttpContentType::TextPlain)
+            .write_to(&mut empty)
+            .unwrap();
+        assert!(empty.ends_with(b"Transfer-Encoding: chunked\r\n\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_no_content_response_has_no_body() {
+        let mut response = HttpResponse::new(
+            HttpStatusCode::NoContent,
+            Some(HttpContent {
+                content: b"ignored".to_vec(),
+                content_type: HttpContentType::TextPlain,
+            }),
+        );
+        response.date = SystemTime::UNIX_EPOCH;
+        response.server = None;
+
+        assert_eq!(
+            String::from_utf8(response.to_bytes()).unwrap(),
+            "HTTP/1.1 204 No Content\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_response_with_binary_body() {
+        let response = HttpResponse::new(
+            HttpStatusCode::Ok,
+            Some(HttpContent {
+                content: vec![0x89, b'P', b'N', b'G', 0xff, 0x00],
+                content_type: HttpContentType::ApplicationOctetStream,
+            }),
+        );
+        let bytes = response.to_bytes();
+
+        assert!(bytes.ends_with(b"Content-Length: 6\r\n\r\n\x89PNG\xff\x00"));
+    }
+
+    fn echo_response(message: &str) -> HttpResponse {
+        HttpResponse::new(
+            HttpStatusCode::Ok,
+            Some(HttpContent {
+                content: message.as_bytes().to_vec(),
+                content_type: HttpContentType::TextPlain,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_compressed_response_round_trips() {
+        let message = "hello ".repeat(100);
+        let response = echo_response(&message).compress(Some("deflate, gzip"), 32);
+
+        assert_eq!(response.header("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.header("Vary"), Some("Accept-Encoding"));
+        let content = response
+            .content
+            .as_ref()
+            .unwrap()
+            .content
+            .as_bytes()
+            .unwrap();
+        assert!(content.len() < message.len());
+        assert_eq!(compression::gunzip(content), message.as_bytes());
+
+        let bytes = response.to_bytes();
+        let expected_length = format!("Content-Length: {}\r\n", content.len());
+        assert!(String::from_utf8_lossy(&bytes).contains(&expected_length));
+
+        let response = echo_response(&message).compress(Some("gzip;q=0.5, deflate"), 32);
+        assert_eq!(response.header("Content-Encoding"), Some("deflate"));
+        let content = response.content.unwrap().content;
+        assert_eq!(
+            compression::unzlib(content.as_bytes().unwrap()),
+            message.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_compress_skips_incompressible_bodies() {
+        let png = HttpResponse::new(
+            HttpStatusCode::Ok,
+            Some(HttpContent {
+                content: vec![0; 1024],
+                content_type: HttpContentType::ImagePng,
+            }),
+        )
+        .compress(Some("gzip"), 32);
+        assert_eq!(png.header("Content-Encoding"), None);
+        assert_eq!(png.header("Vary"), None);
+        assert_eq!(png.content.unwrap().content.as_bytes().unwrap(), [0; 1024]);
+
+        let message = "hello ".repeat(100);
+        let response = echo_response(&message)
+            .without_compression()
+            .compress(Some("gzip"), 32);
+        assert_eq!(response.header("Content-Encoding"), None);
+
+        let response = echo_response(&message)
+            .with_header("Content-Encoding", "br")
+            .compress(Some("gzip"), 32);
+        assert_eq!(response.header("Content-Encoding"), Some("br"));
+        assert_eq!(
+            response.content.unwrap().content.as_bytes().unwrap(),
+            message.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_content_type_is_compressible() {
+        assert!(HttpContentType::TextHtml.is_compressible());
+        assert!(HttpContentType::ImageSvg.is_compressible());
+        assert!(!HttpContentType::ImageJpeg.is_compressible());
+        assert!(!HttpContentType::ApplicationOctetStream.is_compressible());
+        assert!(HttpContentType::Other(String::from("application/xml")).is_compressible());
+        assert!(!HttpContentType::Other(String::from("image/webp")).is_compressible());
+        assert!(!HttpContentType::Other(String::from("Video/MP4")).is_compressible());
+        assert!(!HttpContentType::Other(String::from("application/zip")).is_compressible());
+    }
+
+    #[test]
+    fn test_compress_skips_small_or_unaccepted_bodies() {
+        let response = echo_response("hi").compress(Some("gzip"), 32);
+        assert_eq!(response.header("Content-Encoding"), None);
+        assert_eq!(response.header("Vary"), None);
+        assert_eq!(response.content.unwrap().content.as_bytes().unwrap(), b"hi");
+
+        let message = "hello ".repeat(100);
+        let response = echo_response(&message).compress(Some("br"), 32);
+        assert_eq!(response.header("Content-Encoding"), None);
+        assert_eq!(response.header("Vary"), Some("Accept-Encoding"));
+
+        let response = echo_response(&message)
+            .with_header("vary", "Origin")
+            .compress(Some("gzip"), 32);
+        assert_eq!(response.header("Vary"), Some("Origin, Accept-Encoding"));
+        let response = response.with_vary("accept-encoding");
+        assert_eq!(response.header("Vary"), Some("Origin, Accept-Encoding"));
+
+        let response = echo_response(&message).compress(None, 32);
+        assert_eq!(response.header("Content-Encoding"), None);
+    }
+
+    #[test]
+    fn test_status_code_display() {
+        assert_eq!(HttpStatusCode::Ok.code(), 200);
+        assert_eq!(HttpStatusCode::Ok.reason(), "OK");
+        assert_eq!(HttpStatusCode::Ok.to_string(), "200 OK");
+        assert_eq!(
+            HttpStatusCode::HttpVersionNotSupported.reason(),
+            "HTTP Version Not Supported"
+        );
+        assert_eq!(
+            HttpStatusCode::RangeNotSatisfiable.to_string(),
+            "416 Range Not Satisfiable"
+        );
+    }
+
+    /// Every status code. [`ensure_listed`] has no wildcard arm, so a new variant
+    /// doesn't compile until it's added there, right next to this list.
+    const ALL_STATUS_CODES: [HttpStatusCode; 23] = [
+        HttpStatusCode::Continue,
+        HttpStatusCode::Ok,
+        HttpStatusCode::Created,
+        HttpStatusCode::NoContent,
+        HttpStatusCode::PartialContent,
+        HttpStatusCode::MovedPermanently,
+        HttpStatusCode::Found,
+        HttpStatusCode::NotModified,
+        HttpStatusCode::BadRequest,
+        HttpStatusCode::Unauthorized,
+        HttpStatusCode::Forbidden,
+        HttpStatusCode::NotFound,
+        HttpStatusCode::MethodNotAllowed,
+        HttpStatusCode::RequestTimeout,
+        HttpStatusCode::Conflict,
+        HttpStatusCode::PayloadTooLarge,
+        HttpStatusCode::RangeNotSatisfiable,
+        HttpStatusCode::TooManyRequests,
+        HttpStatusCode::RequestHeaderFieldsTooLarge,
+        HttpStatusCode::InternalServerError,
+        HttpStatusCode::NotImplemented,
+        HttpStatusCode::ServiceUnavailable,
+        HttpStatusCode::HttpVersionNotSupported,
+    ];
+
+    fn ensure_listed(status_code: HttpStatusCode) {
+        match status_code {
+            HttpStatusCode::Continue
+            | HttpStatusCode::Ok
+            | HttpStatusCode::Created
+            | HttpStatusCode::NoContent
+            | HttpStatusCode::PartialContent
+            | HttpStatusCode::MovedPermanently
+            | HttpStatusCode::Found
+            | HttpStatusCode::NotModified
+            | HttpStatusCode::BadRequest
+            | HttpStatusCode::Unauthorized
+            | HttpStatusCode::Forbidden
+            | HttpStatusCode::NotFound
+            | HttpStatusCode::MethodNotAllowed
+            | HttpStatusCode::RequestTimeout
+            | HttpStatusCode::Conflict
+            | HttpStatusCode::PayloadTooLarge
+            | HttpStatusCode::RangeNotSatisfiable
+            | HttpStatusCode::TooManyRequests
+            | HttpStatusCode::RequestHeaderFieldsTooLarge
+            | HttpStatusCode::InternalServerError
+            | HttpStatusCode::NotImplemented
+            | HttpStatusCode::ServiceUnavailable
+            | HttpStatusCode::HttpVersionNotSupported => {}
+        }
+    }
+
+    #[test]
+    fn test_status_code_round_trip() {
+        for status_code in ALL_STATUS_CODES {
+            ensure_listed(status_code);
+            assert_eq!(
+                HttpStatusCode::from_u16(status_code.code()),
+                Some(status_code)
+            );
+        }
+
+        assert_eq!(HttpStatusCode::NotFound.code(), 404);
+        assert_eq!(HttpStatusCode::from_u16(418), None);
+    }
+
+    #[test]
+    fn test_content_type_round_trip() {
+        let content_types = [
+            HttpContentType::ApplicationFormUrlencoded,
+            HttpContentType::ApplicationJavascript,
+            HttpContentType::ApplicationJson,
+            HttpContentType::ApplicationOctetStream,
+            HttpContentType::ImageGif,
+            HttpContentType::ImageJpeg,
+            HttpContentType::ImagePng,
+            HttpContentType::ImageSvg,
+            HttpContentType::MultipartFormData,
+            HttpContentType::TextCss,
+            HttpContentType::TextHtml,
+            HttpContentType::TextPlain,
+            HttpContentType::Other(String::from("application/wasm")),
+        ];
+
+        for content_type in content_types {
+            assert_eq!(
+                HttpContentType::from_str(&content_type.to_string()),
+                Ok(content_type)
+            );
+        }
+        assert_eq!(
+            HttpContentType::from_str("Text/HTML"),
+            Ok(HttpContentType::TextHtml)
+        );
+    }
 }
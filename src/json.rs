@@ -0,0 +1,452 @@
+//! A small JSON (RFC 8259) parser, enough for handlers to read fields out of request
+//! bodies without pulling in a dependency.
+
+use std::fmt;
+use thiserror::Error;
+
+/// Nesting deeper than this is rejected rather than risk overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Members in the order they appear in the document.
+    Object(Vec<(String, JsonValue)>),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum JsonError {
+    #[error("unexpected end of JSON input")]
+    UnexpectedEnd,
+    #[error("unexpected character {0:?} at byte {1}")]
+    UnexpectedChar(char, usize),
+    #[error("invalid number at byte {0}")]
+    InvalidNumber(usize),
+    #[error("invalid escape sequence at byte {0}")]
+    InvalidEscape(usize),
+    #[error("JSON nested more than {MAX_DEPTH} levels deep")]
+    TooDeep,
+}
+
+impl JsonValue {
+    /// Looks up `key` in an object, returning the first member with that name.
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Serializes the value compactly, with no whitespace between tokens. Numbers that
+/// JSON can't represent (NaN and the infinities) are written as `null`.
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(bool) => write!(f, "{}", bool),
+            JsonValue::Number(number) if number.is_finite() => write!(f, "{}", number),
+            JsonValue::Number(_) => write!(f, "null"),
+            JsonValue::String(string) => write_string(f, string),
+            JsonValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Parses a complete JSON document. Anything but whitespace after the value is an
+/// error.
+pub fn parse_json(body: &str) -> Result<JsonValue, JsonError> {
+    let mut parser = Parser {
+        input: body,
+        pos: 0,
+    };
+
+    let value = parser.parse_value(0)?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        Some(c) => Err(JsonError::UnexpectedChar(c, parser.pos)),
+        None => Ok(value),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, JsonError> {
+        let c = self.peek().ok_or(JsonError::UnexpectedEnd)?;
+        self.pos += c.len_utf8();
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        let pos = self.pos;
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => Err(JsonError::UnexpectedChar(c, pos)),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(JsonError::TooDeep);
+        }
+
+        self.skip_whitespace();
+        match self.peek().ok_or(JsonError::UnexpectedEnd)? {
+            '{' => self.parse_object(depth),
+            '[' => self.parse_array(depth),
+            '"' => self.parse_string().map(JsonValue::String),
+            '-' | '0'..='9' => self.parse_number(),
+            't' => self.parse_literal("true", JsonValue::Bool(true)),
+            'f' => self.parse_literal("false", JsonValue::Bool(false)),
+            'n' => self.parse_literal("null", JsonValue::Null),
+            c => Err(JsonError::UnexpectedChar(c, self.pos)),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, JsonError> {
+        for expected in literal.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let name = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            members.push((name, self.parse_value(depth + 1)?));
+
+            self.skip_whitespace();
+            let pos = self.pos;
+            match self.next()? {
+                ',' => continue,
+                '}' => return Ok(JsonValue::Object(members)),
+                c => return Err(JsonError::UnexpectedChar(c, pos)),
+            }
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value(depth + 1)?);
+
+            self.skip_whitespace();
+            let pos = self.pos;
+            match self.next()? {
+                ',' => continue,
+                ']' => return Ok(JsonValue::Array(values)),
+                c => return Err(JsonError::UnexpectedChar(c, pos)),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            let pos = self.pos;
+            match self.next()? {
+                '"' => return Ok(string),
+                '\\' => string.push(self.parse_escape(pos)?),
+                c if c < ' ' => return Err(JsonError::UnexpectedChar(c, pos)),
+                c => string.push(c),
+            }
+        }
+    }
+
+    /// Decodes the escape after a backslash at `start`, combining `\u` surrogate pairs.
+    fn parse_escape(&mut self, start: usize) -> Result<char, JsonError> {
+        let c = match self.next()? {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let high = self.parse_hex4(start)?;
+                let code = if (0xd800..0xdc00).contains(&high) {
+                    self.expect('\\')
+                        .and_then(|()| self.expect('u'))
+                        .map_err(|_| JsonError::InvalidEscape(start))?;
+                    let low = self.parse_hex4(start)?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(JsonError::InvalidEscape(start));
+                    }
+                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or(JsonError::InvalidEscape(start))?
+            }
+            _ => return Err(JsonError::InvalidEscape(start)),
+        };
+        Ok(c)
+    }
+
+    fn parse_hex4(&mut self, start: usize) -> Result<u32, JsonError> {
+        let hex = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .ok_or(JsonError::InvalidEscape(start))?;
+        self.pos += 4;
+        u32::from_str_radix(hex, 16).map_err(|_| JsonError::InvalidEscape(start))
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, JsonError> {
+        let start = self.pos;
+        let bytes = self.input.as_bytes();
+        let digits = |pos: &mut usize| {
+            let from = *pos;
+            while bytes.get(*pos).is_some_and(u8::is_ascii_digit) {
+                *pos += 1;
+            }
+            *pos > from
+        };
+
+        let mut pos = start;
+        if bytes.get(pos) == Some(&b'-') {
+            pos += 1;
+        }
+        if bytes.get(pos) == Some(&b'0') {
+            pos += 1;
+        } else if !digits(&mut pos) {
+            return Err(JsonError::InvalidNumber(start));
+        }
+        if bytes.get(pos) == Some(&b'.') {
+            pos += 1;
+            if !digits(&mut pos) {
+                return Err(JsonError::InvalidNumber(start));
+            }
+        }
+        if let Some(b'e' | b'E') = bytes.get(pos) {
+            pos += 1;
+            if let Some(b'+' | b'-') = bytes.get(pos) {
+                pos += 1;
+            }
+            if !digits(&mut pos) {
+                return Err(JsonError::InvalidNumber(start));
+            }
+        }
+
+        self.pos = pos;
+        self.input[start..pos]
+            .parse()
+            .map(JsonValue::Number)
+            .map_err(|_| JsonError::InvalidNumber(start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scalars() {
+        assert_eq!(parse_json("null"), Ok(JsonValue::Null));
+        assert_eq!(parse_json(" true "), Ok(JsonValue::Bool(true)));
+        assert_eq!(parse_json("false"), Ok(JsonValue::Bool(false)));
+        assert_eq!(parse_json("0"), Ok(JsonValue::Number(0.0)));
+        assert_eq!(parse_json("-12.5e2"), Ok(JsonValue::Number(-1250.0)));
+        assert_eq!(
+            parse_json(r#""a\"b\\c\né😀""#),
+            Ok(JsonValue::String(String::from("a\"b\\c\né😀")))
+        );
+    }
+
+    #[test]
+    fn test_parse_nested() {
+        let value = parse_json(
+            r#"{"name": "report.txt", "size": 42, "tags": ["a", "b"], "owner": {"id": 7, "admin": false}, "parent": null}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value.get("name").and_then(JsonValue::as_str),
+            Some("report.txt")
+        );
+        assert_eq!(value.get("size").and_then(JsonValue::as_f64), Some(42.0));
+        assert_eq!(
+            value.get("tags").and_then(JsonValue::as_array),
+            Some(
+                &[
+                    JsonValue::String(String::from("a")),
+                    JsonValue::String(String::from("b"))
+                ][..]
+            )
+        );
+        let owner = value.get("owner").unwrap();
+        assert_eq!(owner.get("id").and_then(JsonValue::as_f64), Some(7.0));
+        assert_eq!(owner.get("admin").and_then(JsonValue::as_bool), Some(false));
+        assert_eq!(value.get("parent"), Some(&JsonValue::Null));
+        assert_eq!(value.get("missing"), None);
+
+        assert_eq!(parse_json("[]"), Ok(JsonValue::Array(Vec::new())));
+        assert_eq!(parse_json("{ }"), Ok(JsonValue::Object(Vec::new())));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let value = JsonValue::Object(vec![
+            (
+                String::from("name"),
+                JsonValue::String(String::from("a \"b\"\\c\n\u{1}")),
+            ),
+            (String::from("size"), JsonValue::Number(42.0)),
+            (String::from("ratio"), JsonValue::Number(0.5)),
+            (String::from("nan"), JsonValue::Number(f64::NAN)),
+            (
+                String::from("tags"),
+                JsonValue::Array(vec![JsonValue::Bool(true), JsonValue::Null]),
+            ),
+            (String::from("empty"), JsonValue::Object(Vec::new())),
+        ]);
+
+        let json = value.to_string();
+        assert_eq!(
+            json,
+            r#"{"name":"a \"b\"\\c\n\u0001","size":42,"ratio":0.5,"nan":null,"tags":[true,null],"empty":{}}"#
+        );
+
+        let mut expected = value;
+        if let JsonValue::Object(members) = &mut expected {
+            members[3].1 = JsonValue::Null;
+        }
+        assert_eq!(parse_json(&json), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert_eq!(parse_json(""), Err(JsonError::UnexpectedEnd));
+        assert_eq!(parse_json("{\"a\": 1"), Err(JsonError::UnexpectedEnd));
+        assert_eq!(
+            parse_json("{\"a\" 1}"),
+            Err(JsonError::UnexpectedChar('1', 5))
+        );
+        assert_eq!(parse_json("[1,]"), Err(JsonError::UnexpectedChar(']', 3)));
+        assert_eq!(parse_json("01"), Err(JsonError::UnexpectedChar('1', 1)));
+        assert_eq!(parse_json("-"), Err(JsonError::InvalidNumber(0)));
+        assert_eq!(parse_json("1."), Err(JsonError::InvalidNumber(0)));
+        assert_eq!(parse_json(r#""\x""#), Err(JsonError::InvalidEscape(1)));
+        assert_eq!(parse_json(r#""\ud83d""#), Err(JsonError::InvalidEscape(1)));
+        assert_eq!(
+            parse_json("\"a\nb\""),
+            Err(JsonError::UnexpectedChar('\n', 2))
+        );
+        assert_eq!(parse_json("nul"), Err(JsonError::UnexpectedEnd));
+        assert_eq!(
+            parse_json("true false"),
+            Err(JsonError::UnexpectedChar('f', 5))
+        );
+        assert_eq!(parse_json(&"[".repeat(1000)), Err(JsonError::TooDeep));
+    }
+}
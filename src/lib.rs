@@ -0,0 +1,20 @@
+pub mod base64;
+pub mod compression;
+pub mod config;
+pub mod cors;
+pub mod date;
+pub mod error_pages;
+pub mod files;
+pub mod http;
+pub mod json;
+pub mod metrics;
+pub mod middleware;
+pub mod multipart;
+pub mod negotiation;
+pub mod pool;
+pub mod router;
+pub mod routes;
+pub mod server;
+pub mod template;
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -0,0 +1,172 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters for the requests the server has answered, shared by every worker thread.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: AtomicU64,
+    /// Responses by status class, `1xx` through `5xx`.
+    statuses: [AtomicU64; 5],
+    bytes: AtomicU64,
+    open_connections: AtomicU64,
+    connections: AtomicU64,
+}
+
+impl Metrics {
+    /// Counts a response with status `status` and `bytes` bytes of body.
+    pub fn record(&self, status: u16, bytes: u64) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if let Some(count) = (status / 100)
+            .checked_sub(1)
+            .and_then(|class| self.statuses.get(class as usize))
+        {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Counts a connection as open until the returned guard is dropped.
+    pub fn open_connection(&self) -> OpenConnection<'_> {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        self.open_connections.fetch_add(1, Ordering::Relaxed);
+        OpenConnection { metrics: self }
+    }
+
+    /// Connections being served right now.
+    pub fn open_connections(&self) -> u64 {
+        self.open_connections.load(Ordering::Relaxed)
+    }
+
+    /// Connections served since the server started, including the open ones.
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// A one-line summary of the counters, for logging.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} connections open, {} connections and {} requests in total",
+            self.open_connections(),
+            self.connections(),
+            self.requests()
+        )
+    }
+
+    /// Renders the counters in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP http_requests_total Requests answered.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        writeln!(out, "http_requests_total {}", self.requests()).unwrap();
+
+        out.push_str("# HELP http_responses_total Responses sent, by status class.\n");
+        out.push_str("# TYPE http_responses_total counter\n");
+        for (i, count) in self.statuses.iter().enumerate() {
+            writeln!(
+                out,
+                "http_responses_total{{class=\"{}xx\"}} {}",
+                i + 1,
+                count.load(Ordering::Relaxed)
+            )
+            .unwrap();
+        }
+
+        out.push_str("# HELP http_response_bytes_total Response body bytes sent.\n");
+        out.push_str("# TYPE http_response_bytes_total counter\n");
+        writeln!(
+            out,
+            "http_response_bytes_total {}",
+            self.bytes.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        out.push_str("# HELP http_connections_open Connections being served.\n");
+        out.push_str("# TYPE http_connections_open gauge\n");
+        writeln!(out, "http_connections_open {}", self.open_connections()).unwrap();
+
+        out.push_str("# HELP http_connections_total Connections accepted.\n");
+        out.push_str("# TYPE http_connections_total counter\n");
+        writeln!(out, "http_connections_total {}", self.connections()).unwrap();
+
+        out
+    }
+}
+
+/// Keeps a connection counted as open in [`Metrics`] for as long as it's alive.
+#[derive(Debug)]
+pub struct OpenConnection<'a> {
+    metrics: &'a Metrics,
+}
+
+impl Drop for OpenConnection<'_> {
+    fn drop(&mut self) {
+        self.metrics
+            .open_connections
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let metrics = Metrics::default();
+        metrics.record(200, 5);
+        metrics.record(204, 0);
+        metrics.record(404, 0);
+        metrics.record(503, 10);
+        // Not a real status, so it only counts towards the totals.
+        metrics.record(99, 1);
+
+        assert_eq!(metrics.requests(), 5);
+        assert_eq!(
+            metrics.render(),
+            "# HELP http_requests_total Requests answered.\n\
+             # TYPE http_requests_total counter\n\
+             http_requests_total 5\n\
+             # HELP http_responses_total Responses sent, by status class.\n\
+             # TYPE http_responses_total counter\n\
+             http_responses_total{class=\"1xx\"} 0\n\
+             http_responses_total{class=\"2xx\"} 2\n\
+             http_responses_total{class=\"3xx\"} 0\n\
+             http_responses_total{class=\"4xx\"} 1\n\
+             http_responses_total{class=\"5xx\"} 1\n\
+             # HELP http_response_bytes_total Response body bytes sent.\n\
+             # TYPE http_response_bytes_total counter\n\
+             http_response_bytes_total 16\n\
+             # HELP http_connections_open Connections being served.\n\
+             # TYPE http_connections_open gauge\n\
+             http_connections_open 0\n\
+             # HELP http_connections_total Connections accepted.\n\
+             # TYPE http_connections_total counter\n\
+             http_connections_total 0\n"
+        );
+    }
+
+    #[test]
+    fn test_connections() {
+        let metrics = Metrics::default();
+        let first = metrics.open_connection();
+        {
+            let _second = metrics.open_connection();
+            assert_eq!(metrics.open_connections(), 2);
+        }
+        assert_eq!(metrics.open_connections(), 1);
+        drop(first);
+
+        assert_eq!(metrics.open_connections(), 0);
+        assert_eq!(metrics.connections(), 2);
+        metrics.record(200, 0);
+        assert_eq!(
+            metrics.summary(),
+            "0 connections open, 2 connections and 1 requests in total"
+        );
+    }
+}
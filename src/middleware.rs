@@ -0,0 +1,294 @@
+//! Middleware for [`Router::wrap`](crate::router::Router::wrap).
+
+use crate::base64;
+use crate::http::{HttpRequest, HttpResponse, HttpStatusCode};
+use crate::router::Next;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often [`RateLimiter`] drops the buckets of clients it hasn't heard from lately.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Answers a handler error with `500 Internal Server Error`, logging it to stderr along
+/// with the request that caused it.
+pub fn log_errors(request: &HttpRequest, next: Next) -> anyhow::Result<HttpResponse> {
+    next(request).or_else(|e| {
+        eprintln!("error: {} {}: {:#}", request.method, request.path, e);
+        Ok(HttpResponse::new(HttpStatusCode::InternalServerError, None))
+    })
+}
+
+/// HTTP Basic authentication for every path under `prefix`.
+///
+/// Register it with `router.wrap(move |request, next| auth.check(request, next))`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicAuth {
+    /// Shown by browsers when they ask for credentials.
+    pub realm: String,
+    /// Paths starting with this are protected, or every path for `/`.
+    pub prefix: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl BasicAuth {
+    /// Passes the request on if it's outside `prefix` or carries the right
+    /// credentials, and otherwise answers `401 Unauthorized` with a challenge.
+    pub fn check(&self, request: &HttpRequest, next: Next) -> anyhow::Result<HttpResponse> {
+        if !request.path.starts_with(&self.prefix) || self.authorized(request) {
+            return next(request);
+        }
+
+        let challenge = format!("Basic realm=\"{}\"", self.realm.replace('"', "\\\""));
+        Ok(HttpResponse::new(HttpStatusCode::Unauthorized, None)
+            .with_header("WWW-Authenticate", &challenge))
+    }
+
+    fn authorized(&self, request: &HttpRequest) -> bool {
+        let Some(credentials) = request
+            .header("Authorization")
+            .and_then(|authorization| authorization.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Basic"))
+            .and_then(|(_, credentials)| base64::decode(credentials.trim()).ok())
+        else {
+            return false;
+        };
+
+        let expected = format!("{}:{}", self.username, self.password);
+        constant_time_eq(&credentials, expected.as_bytes())
+    }
+}
+
+/// Per-client rate limiting: a token bucket for each IP address, holding up to `burst`
+/// requests and refilled at `rate` a second. A request that finds its bucket empty is
+/// answered `429 Too Many Requests`, with a `Retry-After` saying when there will be a
+/// token for it.
+///
+/// Register it with `router.wrap(move |request, next| limiter.check(request, next))`.
+/// Requests without a remote address aren't limited.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+    /// When the buckets were last cleaned up, locked only while `buckets` is.
+    last_cleanup: Mutex<Instant>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// Adds the tokens refilled since the bucket was last updated.
+    fn refill(&mut self, now: Instant, rate: f64, burst: f64) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.updated = now;
+    }
+}
+
+impl RateLimiter {
+    /// Allows each client `burst` requests at once, and `rate` a second after that.
+    pub fn new(rate: f64, burst: u32) -> RateLimiter {
+        RateLimiter {
+            rate,
+            burst: burst.into(),
+            buckets: Mutex::new(HashMap::new()),
+            last_cleanup: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Passes the request on if its client has a token to spend, and otherwise answers
+    /// `429 Too Many Requests`.
+    pub fn check(&self, request: &HttpRequest, next: Next) -> anyhow::Result<HttpResponse> {
+        let Some(addr) = request.remote_addr else {
+            return next(request);
+        };
+
+        match self.take(addr.ip(), Instant::now()) {
+            Ok(()) => next(request),
+            Err(retry_after) => Ok(HttpResponse::empty(HttpStatusCode::TooManyRequests)
+                .with_header("Retry-After", &retry_after.as_secs().to_string())),
+        }
+    }
+
+    /// Spends a token from `ip`'s bucket, or says how long until there is one, in whole
+    /// seconds rounded up.
+    fn take(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        self.clean_up(&mut buckets, now);
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.refill(now, self.rate, self.burst);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let wait = (1.0 - bucket.tokens) / self.rate;
+        Err(Duration::from_secs(wait.ceil() as u64))
+    }
+
+    /// Every [`CLEANUP_INTERVAL`], forgets the clients whose buckets have refilled, so
+    /// one-off clients don't pile up. A full bucket is what a new client gets anyway.
+    fn clean_up(&self, buckets: &mut HashMap<IpAddr, Bucket>, now: Instant) {
+        let mut last_cleanup = self.last_cleanup.lock().unwrap();
+        if now.saturating_duration_since(*last_cleanup) < CLEANUP_INTERVAL {
+            return;
+        }
+        *last_cleanup = now;
+
+        buckets.retain(|_, bucket| {
+            bucket.refill(now, self.rate, self.burst);
+            bucket.tokens < self.burst
+        });
+    }
+}
+
+/// Compares without stopping at the first difference, so the time taken doesn't tell
+/// an attacker how much of a guess was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpMethod;
+    use crate::router::Router;
+
+    #[test]
+    fn test_log_errors() {
+        let mut router = Router::new();
+        router.add(HttpMethod::Get, "/fail", |_, _| anyhow::bail!("broken"));
+        router.add(HttpMethod::Get, "/ok", |_, _| {
+            Ok(HttpResponse::new(HttpStatusCode::NoContent, None))
+        });
+        router.wrap(log_errors);
+        let route = |path: &str| {
+            let raw_request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+            router.route(&HttpRequest::new(raw_request.as_bytes()).unwrap())
+        };
+
+        assert!(matches!(
+            route("/fail").unwrap().status_code,
+            HttpStatusCode::InternalServerError
+        ));
+        assert!(matches!(
+            route("/ok").unwrap().status_code,
+            HttpStatusCode::NoContent
+        ));
+    }
+
+    #[test]
+    fn test_basic_auth() {
+        let auth = BasicAuth {
+            realm: String::from("files"),
+            prefix: String::from("/files/"),
+            username: String::from("aladdin"),
+            password: String::from("opensesame"),
+        };
+        let mut router = Router::new();
+        router.add(HttpMethod::Get, "/*path", |_, _| {
+            Ok(HttpResponse::new(HttpStatusCode::NoContent, None))
+        });
+        router.wrap(move |request, next| auth.check(request, next));
+        let route = |path: &str, authorization: Option<&str>| {
+            let authorization = authorization
+                .map(|value| format!("Authorization: {}\r\n", value))
+                .unwrap_or_default();
+            let raw_request = format!("GET {} HTTP/1.1\r\n{}\r\n", path, authorization);
+            router
+                .route(&HttpRequest::new(raw_request.as_bytes()).unwrap())
+                .unwrap()
+        };
+
+        let response = route("/files/a.txt", Some("Basic YWxhZGRpbjpvcGVuc2VzYW1l"));
+        assert!(matches!(response.status_code, HttpStatusCode::NoContent));
+        let response = route("/files/a.txt", Some("basic YWxhZGRpbjpvcGVuc2VzYW1l"));
+        assert!(matches!(response.status_code, HttpStatusCode::NoContent));
+
+        for authorization in [
+            None,
+            Some("Basic YWxhZGRpbjp3cm9uZw=="),
+            Some("Basic not base64"),
+            Some("Bearer YWxhZGRpbjpvcGVuc2VzYW1l"),
+        ] {
+            let response = route("/files/a.txt", authorization);
+            assert!(matches!(response.status_code, HttpStatusCode::Unauthorized));
+            assert_eq!(
+                response.header("WWW-Authenticate"),
+                Some("Basic realm=\"files\"")
+            );
+        }
+
+        let response = route("/public", None);
+        assert!(matches!(response.status_code, HttpStatusCode::NoContent));
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(0.5, 3);
+        let mut router = Router::new();
+        router.add(HttpMethod::Get, "/", |_, _| {
+            Ok(HttpResponse::new(HttpStatusCode::NoContent, None))
+        });
+        router.wrap(move |request, next| limiter.check(request, next));
+        let route = |remote_addr: Option<[u8; 4]>| {
+            let mut request = HttpRequest::new(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+            request.remote_addr = remote_addr.map(|ip| (ip, 50000).into());
+            router.route(&request).unwrap()
+        };
+
+        for _ in 0..3 {
+            let response = route(Some([10, 0, 0, 1]));
+            assert!(matches!(response.status_code, HttpStatusCode::NoContent));
+        }
+        let response = route(Some([10, 0, 0, 1]));
+        assert!(matches!(
+            response.status_code,
+            HttpStatusCode::TooManyRequests
+        ));
+        assert_eq!(response.header("Retry-After"), Some("2"));
+
+        // Other clients have buckets of their own, and ones without an address aren't
+        // limited at all.
+        let response = route(Some([10, 0, 0, 2]));
+        assert!(matches!(response.status_code, HttpStatusCode::NoContent));
+        for _ in 0..5 {
+            let response = route(None);
+            assert!(matches!(response.status_code, HttpStatusCode::NoContent));
+        }
+    }
+
+    #[test]
+    fn test_rate_limiter_refills_and_cleans_up() {
+        let limiter = RateLimiter::new(2.0, 2);
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let start = Instant::now();
+
+        assert_eq!(limiter.take(ip, start), Ok(()));
+        assert_eq!(limiter.take(ip, start), Ok(()));
+        assert_eq!(limiter.take(ip, start), Err(Duration::from_secs(1)));
+        assert_eq!(limiter.take(ip, start + Duration::from_millis(500)), Ok(()));
+        assert_eq!(
+            limiter.take(ip, start + Duration::from_millis(500)),
+            Err(Duration::from_secs(1))
+        );
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+
+        // By the next cleanup the bucket has long since refilled, so it's dropped.
+        let later = start + CLEANUP_INTERVAL * 2;
+        assert_eq!(limiter.take(IpAddr::from([10, 0, 0, 1]), later), Ok(()));
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert!(!buckets.contains_key(&ip));
+    }
+}
@@ -0,0 +1,139 @@
+//! `multipart/form-data` bodies (RFC 7578), as browsers send for file uploads.
+
+use crate::http::{parse_media_type, HttpContentType, HttpHeaders};
+
+/// One field of a multipart form.
+#[derive(Debug, PartialEq)]
+pub struct Part {
+    /// The form field's name, from `Content-Disposition`.
+    pub name: Option<String>,
+    /// The uploaded file's name, for file fields.
+    pub filename: Option<String>,
+    pub content_type: Option<HttpContentType>,
+    pub headers: HttpHeaders,
+    pub content: Vec<u8>,
+}
+
+/// Splits `body` into its parts on `boundary`, taken from the `Content-Type` header.
+/// Anything before the first boundary or after the closing `--boundary--` is ignored,
+/// as is a final part cut off before its boundary.
+pub fn parse_multipart(body: &[u8], boundary: &str) -> Vec<Part> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+
+    let Some(start) = find(body, delimiter.as_bytes()) else {
+        return parts;
+    };
+    let mut rest = &body[start + delimiter.len()..];
+
+    // Each part is preceded by a delimiter line and followed by a line break before the
+    // next delimiter.
+    while !rest.starts_with(b"--") {
+        let Some(line_end) = find(rest, b"\n") else {
+            break;
+        };
+        rest = &rest[line_end + 1..];
+
+        let Some(end) = find(rest, delimiter.as_bytes()) else {
+            break;
+        };
+        let part = &rest[..end];
+        let part = part.strip_suffix(b"\n").unwrap_or(part);
+        let part = part.strip_suffix(b"\r").unwrap_or(part);
+        parts.extend(parse_part(part));
+
+        rest = &rest[end + delimiter.len()..];
+    }
+
+    parts
+}
+
+fn parse_part(part: &[u8]) -> Option<Part> {
+    let (header_bytes, content) = match find(part, b"\r\n\r\n") {
+        _ if part.starts_with(b"\r\n") => (&[][..], &part[2..]),
+        Some(end) => (&part[..end], &part[end + 4..]),
+        None => {
+            let end = find(part, b"\n\n")?;
+            (&part[..end], &part[end + 2..])
+        }
+    };
+
+    let mut headers = HttpHeaders::default();
+    for line in std::str::from_utf8(header_bytes).ok()?.lines() {
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name, value.trim());
+        }
+    }
+
+    let (name, filename) = match headers.get("Content-Disposition").map(parse_media_type) {
+        Some((_, mut params)) => (params.remove("name"), params.remove("filename")),
+        None => (None, None),
+    };
+    let content_type = headers
+        .get("Content-Type")
+        .map(|content_type| parse_media_type(content_type).0);
+
+    Some(Part {
+        name,
+        filename,
+        content_type,
+        headers,
+        content: content.to_vec(),
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multipart() {
+        let body = b"preamble\r\n\
+            --XyZ\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\
+            \r\n\
+            Holiday\r\n\
+            --XyZ\r\n\
+            Content-Disposition: form-data; name=\"photo\"; filename=\"beach.png\"\r\n\
+            Content-Type: image/png\r\n\
+            \r\n\
+            \x89PNG\r\n\x1a\n\x00\r\n\
+            --XyZ--\r\n\
+            epilogue";
+
+        let parts = parse_multipart(body, "XyZ");
+        assert_eq!(parts.len(), 2);
+
+        assert_eq!(parts[0].name.as_deref(), Some("title"));
+        assert_eq!(parts[0].filename, None);
+        assert_eq!(parts[0].content_type, None);
+        assert_eq!(parts[0].content, b"Holiday");
+
+        assert_eq!(parts[1].name.as_deref(), Some("photo"));
+        assert_eq!(parts[1].filename.as_deref(), Some("beach.png"));
+        assert_eq!(parts[1].content_type, Some(HttpContentType::ImagePng));
+        assert_eq!(parts[1].content, b"\x89PNG\r\n\x1a\n\x00");
+        assert_eq!(
+            parts[1].headers.get("content-disposition"),
+            Some("form-data; name=\"photo\"; filename=\"beach.png\"")
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_multipart() {
+        assert_eq!(parse_multipart(b"no boundary here", "XyZ"), []);
+        assert_eq!(parse_multipart(b"--XyZ--\r\n", "XyZ"), []);
+
+        // A part without headers is still a part; one cut off before its boundary isn't.
+        let parts = parse_multipart(b"--XyZ\r\n\r\nbare\r\n--XyZ\r\n\r\ncut off", "XyZ");
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].name, None);
+        assert_eq!(parts[0].content, b"bare");
+    }
+}
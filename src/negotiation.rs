@@ -0,0 +1,155 @@
+//! Content negotiation: picking which of the representations a handler can produce
+//! suits the client best, going by its `Accept` header. A response chosen this way
+//! should say so with [`HttpResponse::with_vary`](crate::http::HttpResponse::with_vary)
+//! and `Accept`, so caches keep the representations apart.
+
+use crate::http::{parse_media_type, HttpContentType};
+
+/// One entry of an `Accept` header: a media type, or a range like `text/*` or `*/*`,
+/// and how much the client wants it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaRange {
+    /// The type and subtype, lowercased.
+    pub media_type: String,
+    /// The `q` weight, from 0 to 1.
+    pub q: f32,
+}
+
+impl MediaRange {
+    /// Whether `content_type` falls in this range.
+    fn matches(&self, content_type: &str) -> bool {
+        match self.media_type.strip_suffix("/*") {
+            Some("*") => true,
+            Some(main_type) => content_type
+                .split_once('/')
+                .is_some_and(|(ty, _)| ty == main_type),
+            None => self.media_type == content_type,
+        }
+    }
+
+    /// How specific the range is: an exact type beats `type/*`, which beats `*/*`.
+    fn specificity(&self) -> u8 {
+        match self.media_type.as_str() {
+            "*/*" => 0,
+            range if range.ends_with("/*") => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Parses an `Accept` header value into its media ranges, most wanted first. Ranges
+/// with the same weight keep the order they were sent in, and one with a `q` that
+/// isn't a number is left out.
+pub fn parse_accept(accept: &str) -> Vec<MediaRange> {
+    let mut ranges = Vec::new();
+    for range in accept.split(',').filter(|range| !range.trim().is_empty()) {
+        let (media_type, params) = parse_media_type(range);
+        let q = match params.get("q") {
+            Some(q) => match q.parse::<f32>() {
+                Ok(q) => q.clamp(0.0, 1.0),
+                Err(_) => continue,
+            },
+            None => 1.0,
+        };
+        ranges.push(MediaRange {
+            media_type: media_type.to_string().to_ascii_lowercase(),
+            q,
+        });
+    }
+
+    ranges.sort_by(|a, b| b.q.total_cmp(&a.q));
+    ranges
+}
+
+/// Picks the type from `offered` that an `Accept` header value weighs highest, with
+/// the earlier one winning ties. Each type takes the weight of the most specific
+/// range it falls in, and `None` means none of them are acceptable.
+pub fn negotiate(accept: &str, offered: &[HttpContentType]) -> Option<HttpContentType> {
+    let ranges = parse_accept(accept);
+
+    let weight = |content_type: &HttpContentType| {
+        let content_type = content_type.to_string().to_ascii_lowercase();
+        ranges
+            .iter()
+            .filter(|range| range.matches(&content_type))
+            .max_by_key(|range| range.specificity())
+            .map_or(0.0, |range| range.q)
+    };
+
+    let mut best: Option<(&HttpContentType, f32)> = None;
+    for content_type in offered {
+        let q = weight(content_type);
+        let better = match best {
+            Some((_, best_q)) => q > best_q,
+            None => q > 0.0,
+        };
+        if better {
+            best = Some((content_type, q));
+        }
+    }
+    best.map(|(content_type, _)| content_type.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accept() {
+        assert_eq!(
+            parse_accept("text/html;q=0.5, application/json, */*;q=0.1, text/*;q=0.5"),
+            [
+                MediaRange {
+                    media_type: String::from("application/json"),
+                    q: 1.0
+                },
+                MediaRange {
+                    media_type: String::from("text/html"),
+                    q: 0.5
+                },
+                MediaRange {
+                    media_type: String::from("text/*"),
+                    q: 0.5
+                },
+                MediaRange {
+                    media_type: String::from("*/*"),
+                    q: 0.1
+                },
+            ]
+        );
+        assert_eq!(parse_accept("text/plain;q=high, , Text/HTML").len(), 1);
+        assert!(parse_accept("").is_empty());
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let offered = [HttpContentType::TextPlain, HttpContentType::ApplicationJson];
+
+        assert_eq!(
+            negotiate("application/json, text/plain;q=0.9", &offered),
+            Some(HttpContentType::ApplicationJson)
+        );
+        assert_eq!(
+            negotiate("text/*, application/json;q=0.5", &offered),
+            Some(HttpContentType::TextPlain)
+        );
+        assert_eq!(negotiate("image/png", &offered), None);
+        assert_eq!(negotiate("application/json;q=0", &offered[1..]), None);
+    }
+
+    #[test]
+    fn test_negotiate_wildcard() {
+        let offered = [HttpContentType::ApplicationJson, HttpContentType::TextPlain];
+
+        // Anything goes, so the first type offered wins.
+        assert_eq!(
+            negotiate("*/*", &offered),
+            Some(HttpContentType::ApplicationJson)
+        );
+        // A more specific range overrides the wildcard.
+        assert_eq!(
+            negotiate("*/*, application/json;q=0.2", &offered),
+            Some(HttpContentType::TextPlain)
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A fixed set of worker threads fed from a bounded job queue. Submitting a job
+/// while the queue is full blocks until a worker frees up a slot.
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: Option<SyncSender<Job>>,
+}
+
+impl ThreadPool {
+    /// Creates a pool of `size` workers sharing a queue of up to `queue_capacity`
+    /// pending jobs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn new(size: usize, queue_capacity: usize) -> ThreadPool {
+        assert!(size > 0, "thread pool needs at least one worker");
+
+        let (sender, receiver) = mpsc::sync_channel(queue_capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..size)
+            .map(|id| Worker::new(id, Arc::clone(&receiver)))
+            .collect();
+
+        ThreadPool {
+            workers,
+            sender: Some(sender),
+        }
+    }
+
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.sender
+            .as_ref()
+            .expect("thread pool already shut down")
+            .send(Box::new(f))
+            .expect("all thread pool workers have exited");
+    }
+}
+
+impl Drop for ThreadPool {
+    /// Closes the queue and waits for the workers to finish every job already queued.
+    fn drop(&mut self) {
+        drop(self.sender.take());
+
+        for worker in self.workers.drain(..) {
+            if worker.thread.join().is_err() {
+                eprintln!("error: worker {} panicked", worker.id);
+            }
+        }
+    }
+}
+
+struct Worker {
+    id: usize,
+    thread: JoinHandle<()>,
+}
+
+impl Worker {
+    fn new(id: usize, receiver: Arc<Mutex<Receiver<Job>>>) -> Worker {
+        let thread = thread::spawn(move || loop {
+            let job = receiver
+                .lock()
+                .expect("thread pool queue lock poisoned")
+                .recv();
+
+            match job {
+                Ok(job) => {
+                    // A panicking job must not take the worker down with it.
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        eprintln!("error: job on worker {} panicked", id);
+                    }
+                }
+                Err(_) => break,
+            }
+        });
+
+        Worker { id, thread }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_runs_more_jobs_than_workers() {
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let pool = ThreadPool::new(2, 1);
+        for _ in 0..20 {
+            let counter = Arc::clone(&counter);
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(1));
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        drop(pool);
+
+        assert_eq!(counter.load(Ordering::SeqCst), 20);
+    }
+
+    #[test]
+    fn test_survives_panicking_job() {
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let pool = ThreadPool::new(1, 1);
+        pool.execute(|| panic!("job failed"));
+        let job_counter = Arc::clone(&counter);
+        pool.execute(move || {
+            job_counter.fetch_add(1, Ordering::SeqCst);
+        });
+        drop(pool);
+
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+}
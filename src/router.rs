@@ -0,0 +1,686 @@
+use crate::http::{
+    HttpContent, HttpContentType, HttpMethod, HttpRequest, HttpResponse, HttpStatusCode,
+    RequestTarget,
+};
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Values captured from the `:name` and `*name` segments of a route pattern.
+pub type Params = HashMap<String, String>;
+
+pub type Handler = Box<dyn Fn(&HttpRequest, &Params) -> anyhow::Result<HttpResponse> + Send + Sync>;
+
+/// The rest of the chain a middleware wraps: later middleware, then routing.
+pub type Next<'a> = &'a dyn Fn(&HttpRequest) -> anyhow::Result<HttpResponse>;
+
+pub type Middleware = Box<dyn Fn(&HttpRequest, Next) -> anyhow::Result<HttpResponse> + Send + Sync>;
+
+/// Dispatches requests to handlers registered by method and path pattern.
+///
+/// Patterns are matched segment by segment: literal segments must match exactly and
+/// segments prefixed with `:` capture whatever non-empty segment is in their place. A
+/// final segment prefixed with `*` captures the rest of the path, slashes included.
+///
+/// Static content registered with [`Router::serve_static`] is looked up by exact path
+/// before any pattern is tried.
+///
+/// Every request passes through the middleware registered with [`Router::wrap`] on its
+/// way to the handler, each able to change the response or answer in its place.
+///
+/// Requests for a virtual host registered with [`Router::add_host`] are handed to that
+/// host's router instead, so this one acts as the default for any other host.
+///
+/// Whether a trailing slash matters is up to the router's [`TrailingSlash`] policy.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    statics: HashMap<String, HttpContent>,
+    middleware: Vec<Middleware>,
+    /// Routers for virtual hosts, by lowercased host name without the port.
+    hosts: HashMap<String, Router>,
+    trailing_slash: TrailingSlash,
+}
+
+/// What the router does with a path that matches nothing as it is, but would with a
+/// trailing slash added or taken off.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TrailingSlash {
+    /// Nothing: `/a` and `/a/` are different paths.
+    #[default]
+    Strict,
+    /// Answers `301 Moved Permanently`, pointing at the path that matches.
+    Redirect,
+    /// Routes the request as if it had been for the path that matches.
+    Ignore,
+}
+
+impl FromStr for TrailingSlash {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(TrailingSlash::Strict),
+            "redirect" => Ok(TrailingSlash::Redirect),
+            "ignore" => Ok(TrailingSlash::Ignore),
+            _ => Err(()),
+        }
+    }
+}
+
+struct Route {
+    method: HttpMethod,
+    segments: Vec<Segment>,
+    handler: Handler,
+}
+
+enum Segment {
+    Literal(String),
+    Param(String),
+    Rest(String),
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    pub fn add<F>(&mut self, method: HttpMethod, pattern: &str, handler: F)
+    where
+        F: Fn(&HttpRequest, &Params) -> anyhow::Result<HttpResponse> + Send + Sync + 'static,
+    {
+        let segments = split_path(pattern)
+            .map(|segment| {
+                if let Some(name) = segment.strip_prefix(':') {
+                    Segment::Param(name.to_string())
+                } else if let Some(name) = segment.strip_prefix('*') {
+                    Segment::Rest(name.to_string())
+                } else {
+                    Segment::Literal(segment.to_string())
+                }
+            })
+            .collect();
+
+        self.routes.push(Route {
+            method,
+            segments,
+            handler: Box::new(handler),
+        });
+    }
+
+    /// Adds `middleware` to the chain run around every request. The first added is the
+    /// outermost: it sees the request first and the response last. A middleware that
+    /// returns without calling `next` keeps the request from going any further.
+    pub fn wrap<F>(&mut self, middleware: F)
+    where
+        F: Fn(&HttpRequest, Next) -> anyhow::Result<HttpResponse> + Send + Sync + 'static,
+    {
+        self.middleware.push(Box::new(middleware));
+    }
+
+    /// Serves `content` from memory for GET and HEAD requests to exactly `path`, taking
+    /// precedence over any route matching it.
+    pub fn serve_static(
+        &mut self,
+        path: &str,
+        content: impl Into<Vec<u8>>,
+        content_type: HttpContentType,
+    ) {
+        let content = HttpContent {
+            content: content.into(),
+            content_type,
+        };
+        self.statics.insert(path.to_string(), content);
+    }
+
+    /// Sets what happens to a request whose path only matches with a trailing slash
+    /// added or taken off. Virtual hosts keep their own policy.
+    pub fn set_trailing_slash(&mut self, policy: TrailingSlash) {
+        self.trailing_slash = policy;
+    }
+
+    /// Answers requests whose `Host` header names `host`, whatever its port, with
+    /// `router` rather than this router. Host names are compared ignoring case.
+    pub fn add_host(&mut self, host: &str, router: Router) {
+        self.hosts.insert(host.to_ascii_lowercase(), router);
+    }
+
+    /// Runs the request through the middleware chain and then [`Router::dispatch`], or
+    /// hands it to the router for its virtual host if one was added.
+    pub fn route(&self, request: &HttpRequest) -> anyhow::Result<HttpResponse> {
+        let host = request.header("Host").map(host_name);
+        if let Some(router) = host.and_then(|host| self.hosts.get(&host)) {
+            return router.route(request);
+        }
+        self.run_chain(&self.middleware, request)
+    }
+
+    fn run_chain(
+        &self,
+        middleware: &[Middleware],
+        request: &HttpRequest,
+    ) -> anyhow::Result<HttpResponse> {
+        match middleware.split_first() {
+            Some((first, rest)) => first(request, &|request| self.run_chain(rest, request)),
+            None => self.dispatch(request),
+        }
+    }
+
+    /// Runs the handler registered for the request's method and path, falling back to
+    /// the GET handler for a HEAD request. Answers `404 Not Found` when no pattern
+    /// matches the path, or `405 Method Not Allowed` with an `Allow` header listing the
+    /// registered methods when one does but not for this method.
+    ///
+    /// OPTIONS requests without a handler of their own get a `204 No Content` with the
+    /// `Allow` header, listing every method the server supports for `OPTIONS *`.
+    ///
+    /// A path that matches nothing, but would with its trailing slash toggled, is
+    /// redirected or routed as that path if the [`TrailingSlash`] policy says so.
+    fn dispatch(&self, request: &HttpRequest) -> anyhow::Result<HttpResponse> {
+        let path = request.path.as_str();
+        if self.trailing_slash == TrailingSlash::Strict || self.matches(path) {
+            return self.dispatch_path(request, path);
+        }
+
+        match toggle_trailing_slash(path).filter(|other| self.matches(other)) {
+            Some(other) if self.trailing_slash == TrailingSlash::Ignore => {
+                self.dispatch_path(request, &other)
+            }
+            Some(_) => {
+                // Built from the target as sent, so it stays percent-encoded.
+                let target = request.target.path_and_query();
+                let location = match target.split_once('?') {
+                    Some((path, query)) => {
+                        format!(
+                            "{}?{}",
+                            toggle_trailing_slash(path).unwrap_or_default(),
+                            query
+                        )
+                    }
+                    None => toggle_trailing_slash(target).unwrap_or_default(),
+                };
+                Ok(HttpResponse::redirect(
+                    HttpStatusCode::MovedPermanently,
+                    &location,
+                ))
+            }
+            None => self.dispatch_path(request, path),
+        }
+    }
+
+    /// Whether there is static content or a route, for any method, at `path`.
+    fn matches(&self, path: &str) -> bool {
+        self.statics.contains_key(path)
+            || self
+                .routes
+                .iter()
+                .any(|route| route.match_path(path).is_some())
+    }
+
+    fn dispatch_path(&self, request: &HttpRequest, path: &str) -> anyhow::Result<HttpResponse> {
+        let content = self.statics.get(path);
+        if let Some(content) = content {
+            if matches!(request.method, HttpMethod::Get | HttpMethod::Head) {
+                return Ok(HttpResponse::new(HttpStatusCode::Ok, Some(content.clone())));
+            }
+        }
+
+        let mut get_route = None;
+
+        for route in &self.routes {
+            if let Some(params) = route.match_path(path) {
+                if route.method == request.method {
+                    return (route.handler)(request, &params);
+                }
+                if route.method == HttpMethod::Get && get_route.is_none() {
+                    get_route = Some((route, params));
+                }
+            }
+        }
+
+        if let Some((route, params)) = get_route.filter(|_| request.method == HttpMethod::Head) {
+            return (route.handler)(request, &params);
+        }
+
+        let allowed =
+            if request.method == HttpMethod::Options && request.target == RequestTarget::Asterisk {
+                self.allowed_methods(!self.statics.is_empty(), |_| true)
+            } else {
+                self.allowed_methods(content.is_some(), |route| route.match_path(path).is_some())
+            };
+        if allowed.is_empty() {
+            return Ok(HttpResponse::new(HttpStatusCode::NotFound, None));
+        }
+
+        let status_code = if request.method == HttpMethod::Options {
+            HttpStatusCode::NoContent
+        } else {
+            HttpStatusCode::MethodNotAllowed
+        };
+        let allow = allowed.iter().join(", ");
+        Ok(HttpResponse::new(status_code, None).with_header("Allow", &allow))
+    }
+
+    /// Lists the methods registered on the routes picked by `filter`, in registration
+    /// order, after GET if `static_content` is served. HEAD is answered wherever GET
+    /// is, and OPTIONS wherever anything is.
+    fn allowed_methods(
+        &self,
+        static_content: bool,
+        filter: impl Fn(&Route) -> bool,
+    ) -> Vec<&HttpMethod> {
+        let mut allowed: Vec<&HttpMethod> = Vec::new();
+        let mut allow = |method| {
+            if !allowed.contains(&method) {
+                allowed.push(method);
+            }
+        };
+
+        let mut matched = static_content;
+        if static_content {
+            allow(&HttpMethod::Get);
+            allow(&HttpMethod::Head);
+        }
+        for route in self.routes.iter().filter(|route| filter(route)) {
+            matched = true;
+            allow(&route.method);
+            if route.method == HttpMethod::Get {
+                allow(&HttpMethod::Head);
+            }
+        }
+        if matched {
+            allow(&HttpMethod::Options);
+        }
+
+        allowed
+    }
+}
+
+impl Route {
+    fn match_path(&self, path: &str) -> Option<Params> {
+        let mut params = Params::new();
+        let mut segments = split_path(path);
+
+        for pattern in &self.segments {
+            match pattern {
+                Segment::Rest(name) => {
+                    let rest = segments.by_ref().collect::<Vec<_>>().join("/");
+                    if rest.is_empty() {
+                        return None;
+                    }
+                    params.insert(name.clone(), rest);
+                }
+                Segment::Literal(literal) => {
+                    if segments.next()? != literal {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => match segments.next()? {
+                    "" => return None,
+                    segment => {
+                        params.insert(name.clone(), segment.to_string());
+                    }
+                },
+            }
+        }
+
+        if segments.next().is_some() {
+            return None;
+        }
+        Some(params)
+    }
+}
+
+/// Lowercases the host in a `Host` header value and drops its port, keeping the
+/// brackets of an IPv6 literal.
+fn host_name(host: &str) -> String {
+    let host = host.trim();
+    let name = match host.rfind(':') {
+        Some(colon) if !host[colon..].contains(']') => &host[..colon],
+        _ => host,
+    };
+    name.to_ascii_lowercase()
+}
+
+/// `path` with its trailing slash taken off, or with one added if it has none. `None`
+/// for `/`, which has no other form, and for anything that isn't a path.
+fn toggle_trailing_slash(path: &str) -> Option<String> {
+    if !path.starts_with('/') || path == "/" {
+        return None;
+    }
+    match path.strip_suffix('/') {
+        Some(stripped) => Some(stripped.to_string()),
+        None => Some(format!("{}/", path)),
+    }
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.strip_prefix('/').unwrap_or(path).split('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_response(text: &str) -> anyhow::Result<HttpResponse> {
+        Ok(HttpResponse::new(
+            HttpStatusCode::Ok,
+            Some(HttpContent {
+                content: text.as_bytes().to_vec(),
+                content_type: HttpContentType::TextPlain,
+            }),
+        ))
+    }
+
+    fn router() -> Router {
+        let mut router = Router::new();
+        router.add(HttpMethod::Get, "/", |_, _| text_response("index"));
+        router.add(HttpMethod::Get, "/echo/:msg", |_, params| {
+            text_response(&params["msg"])
+        });
+        router.add(HttpMethod::Post, "/echo/:msg", |_, _| {
+            text_response("posted")
+        });
+        router.add(HttpMethod::Get, "/files/*path", |_, params| {
+            text_response(&params["path"])
+        });
+        router
+    }
+
+    fn route(method: &str, path: &str) -> HttpResponse {
+        let raw_request = format!("{} {} HTTP/1.1\r\n\r\n", method, path);
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+        router().route(&request).unwrap()
+    }
+
+    fn body(response: &HttpResponse) -> &[u8] {
+        response
+            .content
+            .as_ref()
+            .unwrap()
+            .content
+            .as_bytes()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_routes_by_method_and_path() {
+        assert_eq!(body(&route("GET", "/")), b"index");
+        assert_eq!(body(&route("GET", "/echo/hello")), b"hello");
+        assert_eq!(body(&route("POST", "/echo/hello")), b"posted");
+        assert_eq!(body(&route("GET", "/files/a.txt")), b"a.txt");
+        assert_eq!(body(&route("GET", "/files/dir/a.txt")), b"dir/a.txt");
+        assert_eq!(body(&route("HEAD", "/echo/hello")), b"hello");
+    }
+
+    #[test]
+    fn test_unmatched_path_is_not_found() {
+        for path in [
+            "/missing",
+            "/echo",
+            "/echo/",
+            "/echo/a/b",
+            "/files",
+            "/files/",
+        ] {
+            let response = route("GET", path);
+            assert!(
+                matches!(response.status_code, HttpStatusCode::NotFound),
+                "{} should not match",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_wrong_method_is_not_allowed() {
+        let response = route("DELETE", "/echo/hello");
+        assert!(matches!(
+            response.status_code,
+            HttpStatusCode::MethodNotAllowed
+        ));
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, POST, OPTIONS"));
+
+        let response = route("POST", "/files/a.txt");
+        assert!(matches!(
+            response.status_code,
+            HttpStatusCode::MethodNotAllowed
+        ));
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, OPTIONS"));
+    }
+
+    #[test]
+    fn test_options_lists_allowed_methods() {
+        let response = route("OPTIONS", "/echo/hello");
+        assert!(matches!(response.status_code, HttpStatusCode::NoContent));
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, POST, OPTIONS"));
+
+        let response = route("OPTIONS", "*");
+        assert!(matches!(response.status_code, HttpStatusCode::NoContent));
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, POST, OPTIONS"));
+
+        let response = route("OPTIONS", "/missing");
+        assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+    }
+
+    #[test]
+    fn test_static_content() {
+        let mut router = router();
+        router.serve_static(
+            "/robots.txt",
+            "User-agent: *\nDisallow:\n",
+            HttpContentType::TextPlain,
+        );
+        router.serve_static(
+            "/files/pixel.gif",
+            vec![0x47, 0x49, 0x46, 0x00],
+            HttpContentType::ImageGif,
+        );
+        let route = |method: &str, path: &str| {
+            let raw_request = format!("{} {} HTTP/1.1\r\n\r\n", method, path);
+            router
+                .route(&HttpRequest::new(raw_request.as_bytes()).unwrap())
+                .unwrap()
+        };
+
+        let response = route("GET", "/robots.txt");
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+        assert_eq!(body(&response), b"User-agent: *\nDisallow:\n");
+        assert_eq!(
+            response.content.unwrap().content_type,
+            HttpContentType::TextPlain
+        );
+
+        // Static content wins over a route matching the same path.
+        let response = route("HEAD", "/files/pixel.gif");
+        assert_eq!(body(&response), [0x47, 0x49, 0x46, 0x00]);
+        assert_eq!(body(&route("GET", "/files/other.gif")), b"other.gif");
+
+        let response = route("POST", "/robots.txt");
+        assert!(matches!(
+            response.status_code,
+            HttpStatusCode::MethodNotAllowed
+        ));
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, OPTIONS"));
+    }
+
+    #[test]
+    fn test_virtual_hosts() {
+        let mut blog = Router::new();
+        blog.add(HttpMethod::Get, "/", |_, _| text_response("blog"));
+        let mut shop = Router::new();
+        shop.add(HttpMethod::Get, "/", |_, _| text_response("shop"));
+
+        let mut router = router();
+        router.add_host("blog.example.com", blog);
+        router.add_host("Shop.Example.com", shop);
+
+        let body_for = |host: &str| {
+            let raw_request = format!("GET / HTTP/1.1\r\nHost: {}\r\n\r\n", host);
+            let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+            let response = router.route(&request).unwrap();
+            response
+                .content
+                .unwrap()
+                .content
+                .as_bytes()
+                .unwrap()
+                .to_vec()
+        };
+        assert_eq!(body_for("blog.example.com"), b"blog");
+        assert_eq!(body_for("SHOP.example.com:8080"), b"shop");
+        assert_eq!(body_for("other.example.com"), b"index");
+        assert_eq!(body_for("[::1]:4221"), b"index");
+
+        assert_eq!(host_name("Example.com:80"), "example.com");
+        assert_eq!(host_name("[::1]:4221"), "[::1]");
+        assert_eq!(host_name("[::1]"), "[::1]");
+    }
+
+    #[test]
+    fn test_middleware_wraps_handlers() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut router = Router::new();
+        let handler_calls = Arc::clone(&calls);
+        router.add(HttpMethod::Get, "/:page", move |_, params| {
+            handler_calls.fetch_add(1, Ordering::SeqCst);
+            text_response(&params["page"])
+        });
+        router.wrap(|request, next| Ok(next(request)?.with_header("X-Outer", "1")));
+        router.wrap(|request, next| {
+            if request.path == "/private" {
+                return Ok(HttpResponse::new(HttpStatusCode::Forbidden, None));
+            }
+            next(request)
+        });
+        let route = |path: &str| {
+            let raw_request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+            router
+                .route(&HttpRequest::new(raw_request.as_bytes()).unwrap())
+                .unwrap()
+        };
+
+        let response = route("/public");
+        assert_eq!(body(&response), b"public");
+        assert_eq!(response.header("X-Outer"), Some("1"));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // The inner middleware answers without calling the handler, and the outer one
+        // still sees its response.
+        let response = route("/private");
+        assert!(matches!(response.status_code, HttpStatusCode::Forbidden));
+        assert_eq!(response.header("X-Outer"), Some("1"));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    fn match_params(pattern: &str, path: &str) -> Option<Params> {
+        let mut router = Router::new();
+        router.add(HttpMethod::Get, pattern, |_, _| text_response(""));
+        router.routes[0].match_path(path)
+    }
+
+    #[test]
+    fn test_captures_path_params() {
+        let params = match_params("/files/:name", "/files/report.txt").unwrap();
+        assert_eq!(
+            params,
+            Params::from([(String::from("name"), String::from("report.txt"))])
+        );
+
+        let params = match_params("/users/:uid/posts/:pid", "/users/42/posts/7").unwrap();
+        assert_eq!(params["uid"], "42");
+        assert_eq!(params["pid"], "7");
+        assert_eq!(params.len(), 2);
+
+        let params = match_params("/static/*path", "/static/css/site.css").unwrap();
+        assert_eq!(params["path"], "css/site.css");
+    }
+
+    #[test]
+    fn test_non_matching_paths() {
+        assert_eq!(
+            match_params("/users/:uid/posts/:pid", "/users/42/posts"),
+            None
+        );
+        assert_eq!(
+            match_params("/users/:uid/posts/:pid", "/users/42/comments/7"),
+            None
+        );
+        assert_eq!(match_params("/users/:uid", "/users//"), None);
+        assert_eq!(match_params("/files/:name", "/files/report.txt/"), None);
+        assert_eq!(match_params("/files/:name/", "/files/report.txt"), None);
+        assert_eq!(match_params("/static/*path", "/static"), None);
+    }
+
+    fn route_with_policy(policy: TrailingSlash, path: &str) -> HttpResponse {
+        let mut router = router();
+        router.add(HttpMethod::Get, "/dir/", |_, _| text_response("dir"));
+        router.set_trailing_slash(policy);
+        let raw_request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+        router.route(&request).unwrap()
+    }
+
+    #[test]
+    fn test_trailing_slash_strict() {
+        assert_eq!(
+            body(&route_with_policy(TrailingSlash::Strict, "/echo/hello")),
+            b"hello"
+        );
+        let response = route_with_policy(TrailingSlash::Strict, "/echo/hello/");
+        assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+        let response = route_with_policy(TrailingSlash::Strict, "/dir");
+        assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+    }
+
+    #[test]
+    fn test_trailing_slash_redirect() {
+        assert_eq!(
+            body(&route_with_policy(TrailingSlash::Redirect, "/echo/hello")),
+            b"hello"
+        );
+
+        let response = route_with_policy(TrailingSlash::Redirect, "/echo/hello/?x=1");
+        assert!(matches!(
+            response.status_code,
+            HttpStatusCode::MovedPermanently
+        ));
+        assert_eq!(response.header("Location"), Some("/echo/hello?x=1"));
+
+        let response = route_with_policy(TrailingSlash::Redirect, "/dir");
+        assert!(matches!(
+            response.status_code,
+            HttpStatusCode::MovedPermanently
+        ));
+        assert_eq!(response.header("Location"), Some("/dir/"));
+
+        // Neither form matches, so there is nowhere to redirect to.
+        let response = route_with_policy(TrailingSlash::Redirect, "/missing/");
+        assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+    }
+
+    #[test]
+    fn test_trailing_slash_ignore() {
+        assert_eq!(
+            body(&route_with_policy(TrailingSlash::Ignore, "/echo/hello")),
+            b"hello"
+        );
+        assert_eq!(
+            body(&route_with_policy(TrailingSlash::Ignore, "/echo/hello/")),
+            b"hello"
+        );
+        assert_eq!(
+            body(&route_with_policy(TrailingSlash::Ignore, "/dir")),
+            b"dir"
+        );
+        assert_eq!(
+            body(&route_with_policy(TrailingSlash::Ignore, "/dir/")),
+            b"dir"
+        );
+        let response = route_with_policy(TrailingSlash::Ignore, "/missing/");
+        assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+    }
+}
@@ -0,0 +1,926 @@
+use crate::config::Config;
+use crate::date::format_http_date;
+use crate::files::{
+    content_disposition, directory_listing, etag_matches, if_range_matches, index_file,
+    known_content_type, not_modified_since, parse_range, resolve_path, weak_etag, ByteRange,
+    PathError,
+};
+use crate::http::{self, HttpMethod, HttpRequest, HttpResponse, HttpStatusCode};
+use crate::metrics::Metrics;
+use crate::middleware::{self, RateLimiter};
+use crate::multipart::parse_multipart;
+use crate::router::{Params, Router};
+use anyhow::Context;
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Files at least this large are streamed from disk as they're sent rather than read
+/// into memory first, which also means they go out uncompressed.
+const MIN_STREAM_SIZE: u64 = 1024 * 1024;
+
+/// The liveness check load balancers poll, left out of the access log.
+pub const HEALTH_CHECK_PATH: &str = "/healthz";
+
+/// Builds a router with the built-in routes, serving `/files` from the configured
+/// directory and anything else from the document root, and reporting `metrics`.
+pub fn build_router(config: &Config, metrics: &Arc<Metrics>) -> Router {
+    let mut router = Router::new();
+    router.wrap(middleware::log_errors);
+    if let Some(rate) = config.rate_limit {
+        let limiter = RateLimiter::new(rate.into(), rate);
+        router.wrap(move |request, next| limiter.check(request, next));
+    }
+
+    // With a document root, `/` is its index page instead.
+    if config.root.is_none() {
+        router.add(HttpMethod::Get, "/", |_, _| {
+            Ok(HttpResponse::empty(HttpStatusCode::Ok))
+        });
+    }
+    router.add(HttpMethod::Get, "/echo/:msg", |_, params| {
+        Ok(HttpResponse::text(HttpStatusCode::Ok, &params["msg"]))
+    });
+    router.add(HttpMethod::Post, "/echo", |request, _| {
+        let response = HttpResponse::new(HttpStatusCode::Ok, request.content.clone());
+        // The parsed type drops parameters like `charset`, so send back the header
+        // exactly as it came.
+        Ok(match request.header("Content-Type") {
+            Some(content_type) => response.with_header("Content-Type", content_type),
+            None => response,
+        })
+    });
+    router.add(HttpMethod::Get, "/user-agent", |request, _| {
+        Ok(HttpResponse::text(
+            HttpStatusCode::Ok,
+            request.header("User-Agent").unwrap_or(""),
+        ))
+    });
+    let metrics = Arc::clone(metrics);
+    router.add(HttpMethod::Get, "/metrics", move |_, _| {
+        Ok(HttpResponse::text(HttpStatusCode::Ok, &metrics.render()))
+    });
+    if config.health_check {
+        router.add(HttpMethod::Get, HEALTH_CHECK_PATH, |_, _| {
+            Ok(HttpResponse::text(HttpStatusCode::Ok, "ok"))
+        });
+    }
+
+    // Without a directory there's nothing to serve, so `/files` is left unrouted and
+    // answered with 404 like any other unknown path.
+    let options = Arc::new(FileOptions {
+        listing: config.listing,
+        cache_control: config.cache_control.clone(),
+        default_content_type: config.default_content_type.clone(),
+    });
+    if let Some(directory) = &config.directory {
+        let directory = Arc::new(directory.clone());
+        let routes = [
+            (HttpMethod::Get, "/files/*name"),
+            (HttpMethod::Post, "/files/*name"),
+            (HttpMethod::Put, "/files/*name"),
+            (HttpMethod::Delete, "/files/*name"),
+            (HttpMethod::Get, "/files/"),
+        ];
+        for (method, pattern) in routes {
+            let directory = Arc::clone(&directory);
+            let options = Arc::clone(&options);
+            router.add(method, pattern, move |request, params| {
+                handle_file_request(request, params, &directory, &options)
+            });
+        }
+    }
+
+    // Routes are tried in order, so registering these last lets every route above
+    // take precedence over a file of the same name.
+    if let Some(root) = &config.root {
+        let root = Arc::new(root.clone());
+        for pattern in ["/", "/*name"] {
+            let root = Arc::clone(&root);
+            let options = Arc::clone(&options);
+            router.add(HttpMethod::Get, pattern, move |request, params| {
+                handle_file_request(request, params, &root, &options)
+            });
+        }
+    }
+
+    router.set_trailing_slash(config.trailing_slash);
+    router
+}
+
+/// How the file routes serve what they find, taken from the [`Config`].
+struct FileOptions {
+    /// Whether directories without an `index.html` are answered with a listing.
+    listing: bool,
+    /// Sent as the `Cache-Control` header with files, if given.
+    cache_control: Option<String>,
+    /// The type of files whose extension isn't recognised.
+    default_content_type: http::HttpContentType,
+}
+
+/// Serves, writes or deletes the file named by the `name` param. A directory is
+/// answered with its `index.html`, or failing that a listing if `options` allow it.
+fn handle_file_request(
+    request: &HttpRequest,
+    params: &Params,
+    directory: &Path,
+    options: &FileOptions,
+) -> anyhow::Result<HttpResponse> {
+    let name = params.get("name").map_or("", String::as_str);
+    let full_path = match resolve_path(directory, name) {
+        Ok(full_path) => full_path,
+        Err(PathError::Forbidden) => return Ok(HttpResponse::empty(HttpStatusCode::Forbidden)),
+        Err(PathError::NotFound) => return Ok(HttpResponse::empty(HttpStatusCode::NotFound)),
+    };
+
+    let response = if matches!(request.method, HttpMethod::Get | HttpMethod::Head) {
+        let (full_path, content_type) = match index_file(&full_path) {
+            Some(index) => (index, http::HttpContentType::TextHtml),
+            None => {
+                let content_type = known_content_type(name)
+                    .unwrap_or_else(|| options.default_content_type.clone());
+                (full_path, content_type)
+            }
+        };
+
+        if full_path.is_dir() {
+            if !options.listing {
+                return Ok(HttpResponse::empty(HttpStatusCode::NotFound));
+            }
+            let listing = directory_listing(&full_path, &request.path)
+                .with_context(|| format!("failed to list {}", full_path.display()))?;
+            return Ok(HttpResponse::html(HttpStatusCode::Ok, &listing));
+        }
+
+        let response = serve_file(
+            request,
+            &full_path,
+            content_type,
+            options.cache_control.as_deref(),
+        )?;
+        // `?download=1` asks for the file to be saved rather than shown.
+        let download = request.query_param("download") == Some("1");
+        match full_path.file_name() {
+            Some(filename) if download && response.status_code.code() < 300 => {
+                let filename = filename.to_string_lossy();
+                response.with_header("Content-Disposition", &content_disposition(&filename))
+            }
+            _ => response,
+        }
+    } else if request.method == HttpMethod::Post {
+        if request.content.is_none() {
+            return Ok(HttpResponse::empty(HttpStatusCode::BadRequest));
+        }
+
+        // A browser form upload carries the file as one part of a multipart body.
+        let upload = match request.content_type() {
+            Some((http::HttpContentType::MultipartFormData, params)) => {
+                let boundary = params.get("boundary").map_or("", String::as_str);
+                let mut parts = parse_multipart(request.body_bytes(), boundary);
+                match parts.iter().position(|part| part.filename.is_some()) {
+                    Some(i) => Some(parts.swap_remove(i).content),
+                    None => return Ok(HttpResponse::empty(HttpStatusCode::BadRequest)),
+                }
+            }
+            _ => None,
+        };
+        let content = upload.as_deref().unwrap_or(request.body_bytes());
+
+        // POST only creates files; replacing one is what PUT is for.
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&full_path);
+        match file {
+            Ok(mut file) => file
+                .write_all(content)
+                .with_context(|| format!("failed to write {}", full_path.display()))?,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                return Ok(HttpResponse::empty(HttpStatusCode::Conflict))
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to create {}", full_path.display()))
+            }
+        }
+
+        HttpResponse::empty(HttpStatusCode::Created)
+    } else if request.method == HttpMethod::Put {
+        // The check only picks the status: the file is replaced or created either way.
+        let existed = full_path.is_file();
+        File::create(&full_path)
+            .and_then(|mut file| file.write_all(request.body_bytes()))
+            .with_context(|| format!("failed to write {}", full_path.display()))?;
+
+        if existed {
+            HttpResponse::empty(HttpStatusCode::NoContent)
+        } else {
+            HttpResponse::empty(HttpStatusCode::Created)
+        }
+    } else if request.method == HttpMethod::Delete {
+        match std::fs::remove_file(&full_path) {
+            Ok(()) => HttpResponse::empty(HttpStatusCode::NoContent),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                HttpResponse::empty(HttpStatusCode::NotFound)
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to delete {}", full_path.display()))
+            }
+        }
+    } else {
+        HttpResponse::empty(HttpStatusCode::NotFound)
+    };
+
+    Ok(response)
+}
+
+/// Sends the file at `full_path`, honouring conditional and `Range` requests.
+fn serve_file(
+    request: &HttpRequest,
+    full_path: &Path,
+    content_type: http::HttpContentType,
+    cache_control: Option<&str>,
+) -> anyhow::Result<HttpResponse> {
+    let file = match File::open(full_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Ok(HttpResponse::empty(HttpStatusCode::NotFound))
+        }
+        Err(e) => return Err(e).with_context(|| format!("failed to open {}", full_path.display())),
+    };
+    let metadata = file
+        .metadata()
+        .with_context(|| format!("failed to stat {}", full_path.display()))?;
+    let etag = weak_etag(&metadata);
+    let modified = metadata.modified().ok();
+
+    // A 304 repeats the caching headers the full response would have had.
+    let with_validators = |response: HttpResponse| {
+        let mut response = response.with_header("ETag", &etag);
+        if let Some(modified) = modified {
+            response = response.with_header("Last-Modified", &format_http_date(modified));
+        }
+        if let Some(cache_control) = cache_control {
+            response = response.with_header("Cache-Control", cache_control);
+        }
+        response
+    };
+
+    // If-Modified-Since only counts when there's no If-None-Match to go by.
+    let not_modified = match request.header("If-None-Match") {
+        Some(if_none_match) => etag_matches(if_none_match, &etag),
+        None => modified
+            .zip(request.header("If-Modified-Since"))
+            .is_some_and(|(modified, since)| {
+                not_modified_since(modified, since, SystemTime::now())
+            }),
+    };
+    if not_modified {
+        return Ok(with_validators(HttpResponse::empty(
+            HttpStatusCode::NotModified,
+        )));
+    }
+
+    let len = metadata.len();
+    let body = |status_code, start, len| {
+        file_body(status_code, file, start, len, content_type)
+            .with_context(|| format!("failed to read {}", full_path.display()))
+    };
+
+    // A Range sent with an outdated If-Range gets the whole file, since the part the
+    // client is missing may not line up with what it has any more.
+    let range = match request.header("If-Range") {
+        Some(if_range) if !if_range_matches(if_range, &etag, modified, SystemTime::now()) => None,
+        _ => request.header("Range"),
+    };
+    let response = match range.and_then(|range| parse_range(range, len)) {
+        Some(ByteRange::Partial { start, end }) => {
+            body(HttpStatusCode::PartialContent, start, end - start + 1)?
+                .with_header("Content-Range", &format!("bytes {}-{}/{}", start, end, len))
+        }
+        Some(ByteRange::Unsatisfiable) => HttpResponse::empty(HttpStatusCode::RangeNotSatisfiable)
+            .with_header("Content-Range", &format!("bytes */{}", len)),
+        None => body(HttpStatusCode::Ok, 0, len)?,
+    };
+
+    Ok(with_validators(
+        response.with_header("Accept-Ranges", "bytes"),
+    ))
+}
+
+/// Builds a response with `len` bytes of `file` from `start`, streamed if there are at
+/// least [`MIN_STREAM_SIZE`] of them.
+fn file_body(
+    status_code: HttpStatusCode,
+    mut file: File,
+    start: u64,
+    len: u64,
+    content_type: http::HttpContentType,
+) -> io::Result<HttpResponse> {
+    file.seek(SeekFrom::Start(start))?;
+
+    if len >= MIN_STREAM_SIZE {
+        return Ok(HttpResponse::file(status_code, file, len, content_type));
+    }
+
+    let mut content = Vec::new();
+    file.take(len).read_to_end(&mut content)?;
+    Ok(HttpResponse::new(
+        status_code,
+        Some(http::HttpContent {
+            content,
+            content_type,
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{quiet, serving, TempDir};
+
+    #[test]
+    fn test_file_request_rejects_traversal() {
+        let dir = TempDir::new("files");
+        let base = dir.join("root");
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+        std::fs::write(base.join("public.txt"), "public").unwrap();
+
+        for path in [
+            "/files/../secret.txt",
+            "/files/%2e%2e/secret.txt",
+            "/files/..%2Fsecret.txt",
+        ] {
+            let raw_request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+            let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+            let response = build_router(&serving(&base), &Arc::default())
+                .route(&request)
+                .unwrap();
+            assert!(
+                matches!(response.status_code, HttpStatusCode::Forbidden),
+                "{} was not forbidden",
+                path
+            );
+        }
+
+        let request = HttpRequest::new(b"GET /files/public.txt HTTP/1.1\r\n\r\n").unwrap();
+        let response = build_router(&serving(&base), &Arc::default())
+            .route(&request)
+            .unwrap();
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+    }
+
+    #[test]
+    fn test_file_request_write_failure() {
+        let base = TempDir::new("write-failure");
+        std::fs::create_dir_all(base.join("dir")).unwrap();
+
+        let request =
+            HttpRequest::new(b"PUT /files/dir HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+        let response = build_router(&serving(&base), &Arc::default())
+            .route(&request)
+            .unwrap();
+        assert!(matches!(
+            response.status_code,
+            HttpStatusCode::InternalServerError
+        ));
+    }
+
+    #[test]
+    fn test_built_in_routes_disallowed_methods() {
+        let base = TempDir::new("disallowed-methods");
+        let router = build_router(&serving(&base), &Arc::default());
+
+        for (raw_request, allow) in [
+            ("DELETE / HTTP/1.1\r\n\r\n", "GET, HEAD, OPTIONS"),
+            ("POST / HTTP/1.1\r\n\r\n", "GET, HEAD, OPTIONS"),
+            ("PUT /echo/abc HTTP/1.1\r\n\r\n", "GET, HEAD, OPTIONS"),
+            ("GET /echo HTTP/1.1\r\n\r\n", "POST, OPTIONS"),
+            ("POST /user-agent HTTP/1.1\r\n\r\n", "GET, HEAD, OPTIONS"),
+            (
+                "PATCH /files/a.txt HTTP/1.1\r\n\r\n",
+                "GET, HEAD, POST, PUT, DELETE, OPTIONS",
+            ),
+            ("DELETE /files/ HTTP/1.1\r\n\r\n", "GET, HEAD, OPTIONS"),
+        ] {
+            let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+            let response = router.route(&request).unwrap();
+            assert!(
+                matches!(response.status_code, HttpStatusCode::MethodNotAllowed),
+                "{}",
+                raw_request
+            );
+            assert_eq!(response.header("Allow"), Some(allow), "{}", raw_request);
+        }
+    }
+
+    #[test]
+    fn test_files_without_directory() {
+        let router = build_router(&quiet(), &Arc::default());
+        for raw_request in [
+            "GET /files/a.txt HTTP/1.1\r\n\r\n",
+            "POST /files/a.txt HTTP/1.1\r\nContent-Length: 1\r\n\r\na",
+            "GET /files/ HTTP/1.1\r\n\r\n",
+        ] {
+            let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+            let response = router.route(&request).unwrap();
+            assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+        }
+    }
+
+    fn file_request(base: &Path, raw_request: &str) -> HttpResponse {
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+        build_router(&serving(base), &Arc::default())
+            .route(&request)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_file_request_range() {
+        let base = TempDir::new("range");
+        std::fs::write(base.join("digits.txt"), "0123456789").unwrap();
+
+        let response = file_request(
+            &base,
+            "GET /files/digits.txt HTTP/1.1\r\nRange: bytes=2-5\r\n\r\n",
+        );
+        assert!(matches!(
+            response.status_code,
+            HttpStatusCode::PartialContent
+        ));
+        assert_eq!(response.header("Content-Range"), Some("bytes 2-5/10"));
+        assert_eq!(
+            response.content.unwrap().content.as_bytes().unwrap(),
+            b"2345"
+        );
+
+        let response = file_request(
+            &base,
+            "GET /files/digits.txt HTTP/1.1\r\nRange: bytes=10-20\r\n\r\n",
+        );
+        assert!(matches!(
+            response.status_code,
+            HttpStatusCode::RangeNotSatisfiable
+        ));
+        assert_eq!(response.header("Content-Range"), Some("bytes */10"));
+        assert!(response.content.is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_request_if_range() {
+        let base = TempDir::new("if-range");
+        let path = base.join("digits.txt");
+        std::fs::write(&path, "0123456789").unwrap();
+        let if_range = |validator: &str| {
+            file_request(
+                &base,
+                &format!(
+                    "GET /files/digits.txt HTTP/1.1\r\nRange: bytes=2-5\r\nIf-Range: {}\r\n\r\n",
+                    validator
+                ),
+            )
+        };
+
+        // Modified in the second it's served, so its date isn't a strong validator yet.
+        let response = file_request(&base, "GET /files/digits.txt HTTP/1.1\r\n\r\n");
+        let last_modified = response.header("Last-Modified").unwrap().to_string();
+        assert!(matches!(
+            if_range(&last_modified).status_code,
+            HttpStatusCode::Ok
+        ));
+
+        let touched = std::process::Command::new("touch")
+            .args(["-t", "199411060849.37"])
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(touched.success());
+        let response = file_request(&base, "GET /files/digits.txt HTTP/1.1\r\n\r\n");
+        let etag = response.header("ETag").unwrap().to_string();
+        let last_modified = response.header("Last-Modified").unwrap().to_string();
+
+        let response = if_range(&last_modified);
+        assert!(matches!(
+            response.status_code,
+            HttpStatusCode::PartialContent
+        ));
+        assert_eq!(
+            response.content.unwrap().content.as_bytes().unwrap(),
+            b"2345"
+        );
+
+        // Weak entity tags never match, not even the current one.
+        for validator in [
+            etag.as_str(),
+            "W/\"stale\"",
+            "Thu, 01 Jan 2015 00:00:00 GMT",
+        ] {
+            let response = if_range(validator);
+            assert!(matches!(response.status_code, HttpStatusCode::Ok));
+            assert_eq!(response.header("Content-Range"), None);
+            assert_eq!(
+                response.content.unwrap().content.as_bytes().unwrap(),
+                b"0123456789"
+            );
+        }
+    }
+
+    #[test]
+    fn test_file_request_directory_listing() {
+        let base = TempDir::new("listing-request");
+        std::fs::write(base.join("a.txt"), "a").unwrap();
+
+        let response = file_request(&base, "GET /files/ HTTP/1.1\r\n\r\n");
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+        let content = response.content.unwrap();
+        assert_eq!(content.content_type, http::HttpContentType::TextHtml);
+        assert!(
+            String::from_utf8(content.content.as_bytes().unwrap().to_vec())
+                .unwrap()
+                .contains("<a href=\"/files/a.txt\">a.txt</a>")
+        );
+    }
+
+    #[test]
+    fn test_file_request_directory_index() {
+        let base = TempDir::new("index");
+        std::fs::create_dir_all(base.join("site")).unwrap();
+        std::fs::write(base.join("site/index.html"), "<h1>home</h1>").unwrap();
+        std::fs::create_dir_all(base.join("empty")).unwrap();
+
+        let response = file_request(&base, "GET /files/site/ HTTP/1.1\r\n\r\n");
+        let content = response.content.unwrap();
+        assert_eq!(content.content_type, http::HttpContentType::TextHtml);
+        assert_eq!(content.content.as_bytes().unwrap(), b"<h1>home</h1>");
+
+        let response = file_request(&base, "GET /files/empty/ HTTP/1.1\r\n\r\n");
+        assert!(String::from_utf8(
+            response
+                .content
+                .unwrap()
+                .content
+                .as_bytes()
+                .unwrap()
+                .to_vec()
+        )
+        .unwrap()
+        .contains("Index of /files/empty/"));
+
+        let config = Config {
+            directory: Some(base.to_path_buf()),
+            listing: false,
+            ..quiet()
+        };
+        let request = HttpRequest::new(b"GET /files/empty/ HTTP/1.1\r\n\r\n").unwrap();
+        let response = build_router(&config, &Arc::default())
+            .route(&request)
+            .unwrap();
+        assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+    }
+
+    #[test]
+    fn test_file_request_etag() {
+        let base = TempDir::new("etag");
+        std::fs::write(base.join("a.txt"), "hello").unwrap();
+
+        let response = file_request(&base, "GET /files/a.txt HTTP/1.1\r\n\r\n");
+        let etag = response.header("ETag").unwrap().to_string();
+        assert!(etag.starts_with("W/\""));
+
+        let response = file_request(
+            &base,
+            &format!(
+                "GET /files/a.txt HTTP/1.1\r\nIf-None-Match: {}\r\n\r\n",
+                etag
+            ),
+        );
+        assert!(matches!(response.status_code, HttpStatusCode::NotModified));
+        assert_eq!(response.header("ETag"), Some(etag.as_str()));
+        assert!(response.content.is_none());
+
+        let response = file_request(
+            &base,
+            "GET /files/a.txt HTTP/1.1\r\nIf-None-Match: W/\"other\"\r\n\r\n",
+        );
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+        assert_eq!(
+            response.content.unwrap().content.as_bytes().unwrap(),
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn test_file_request_last_modified() {
+        let base = TempDir::new("last-modified");
+        std::fs::write(base.join("a.txt"), "hello").unwrap();
+
+        let response = file_request(&base, "GET /files/a.txt HTTP/1.1\r\n\r\n");
+        let last_modified = response.header("Last-Modified").unwrap().to_string();
+
+        let response = file_request(
+            &base,
+            &format!(
+                "GET /files/a.txt HTTP/1.1\r\nIf-Modified-Since: {}\r\n\r\n",
+                last_modified
+            ),
+        );
+        assert!(matches!(response.status_code, HttpStatusCode::NotModified));
+        assert!(response.content.is_none());
+
+        let response = file_request(
+            &base,
+            "GET /files/a.txt HTTP/1.1\r\nIf-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n",
+        );
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+
+        let response = file_request(
+            &base,
+            "GET /files/a.txt HTTP/1.1\r\nIf-Modified-Since: garbage\r\n\r\n",
+        );
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+    }
+
+    #[test]
+    fn test_file_request_cache_control() {
+        let base = TempDir::new("cache-control");
+        std::fs::write(base.join("a.txt"), "hello").unwrap();
+
+        let response = file_request(&base, "GET /files/a.txt HTTP/1.1\r\n\r\n");
+        assert_eq!(response.header("Cache-Control"), None);
+
+        let config = Config {
+            cache_control: Some(String::from("max-age=3600")),
+            ..serving(&base)
+        };
+        let router = build_router(&config, &Arc::default());
+        let route = |raw_request: &str| {
+            let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+            router.route(&request).unwrap()
+        };
+
+        let response = route("GET /files/a.txt HTTP/1.1\r\n\r\n");
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+        assert_eq!(response.header("Cache-Control"), Some("max-age=3600"));
+
+        let etag = response.header("ETag").unwrap().to_string();
+        let response = route(&format!(
+            "GET /files/a.txt HTTP/1.1\r\nIf-None-Match: {}\r\n\r\n",
+            etag
+        ));
+        assert!(matches!(response.status_code, HttpStatusCode::NotModified));
+        assert_eq!(response.header("Cache-Control"), Some("max-age=3600"));
+
+        // Other routes aren't static files.
+        let response = route("GET /echo/abc HTTP/1.1\r\n\r\n");
+        assert_eq!(response.header("Cache-Control"), None);
+    }
+
+    #[test]
+    fn test_file_request_default_content_type() {
+        let base = TempDir::new("default-content-type");
+        std::fs::write(base.join("server-log"), "started").unwrap();
+        std::fs::write(base.join("a.json"), "{}").unwrap();
+
+        let content_type = |config: &Config, path: &str| {
+            let raw_request = format!("GET /files/{} HTTP/1.1\r\n\r\n", path);
+            let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+            let response = build_router(config, &Arc::default())
+                .route(&request)
+                .unwrap();
+            response.content.unwrap().content_type
+        };
+
+        let config = serving(&base);
+        assert_eq!(
+            content_type(&config, "server-log"),
+            http::HttpContentType::ApplicationOctetStream
+        );
+
+        let config = Config {
+            default_content_type: http::HttpContentType::TextPlain,
+            ..serving(&base)
+        };
+        assert_eq!(
+            content_type(&config, "server-log"),
+            http::HttpContentType::TextPlain
+        );
+        assert_eq!(
+            content_type(&config, "a.json"),
+            http::HttpContentType::ApplicationJson
+        );
+    }
+
+    #[test]
+    fn test_file_request_download() {
+        let base = TempDir::new("download");
+        std::fs::write(base.join("report 1.txt"), "hello").unwrap();
+
+        let response = file_request(&base, "GET /files/report%201.txt HTTP/1.1\r\n\r\n");
+        assert_eq!(response.header("Content-Disposition"), None);
+
+        let response = file_request(
+            &base,
+            "GET /files/report%201.txt?download=1 HTTP/1.1\r\n\r\n",
+        );
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+        assert_eq!(
+            response.header("Content-Disposition"),
+            Some("attachment; filename=\"report 1.txt\"")
+        );
+
+        let response = file_request(&base, "GET /files/missing.txt?download=1 HTTP/1.1\r\n\r\n");
+        assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+        assert_eq!(response.header("Content-Disposition"), None);
+    }
+
+    #[test]
+    fn test_file_request_put() {
+        let base = TempDir::new("put");
+
+        let response = file_request(
+            &base,
+            "PUT /files/a.txt HTTP/1.1\r\nContent-Length: 5\r\n\r\nfirst",
+        );
+        assert!(matches!(response.status_code, HttpStatusCode::Created));
+        assert_eq!(std::fs::read(base.join("a.txt")).unwrap(), b"first");
+
+        let response = file_request(
+            &base,
+            "PUT /files/a.txt HTTP/1.1\r\nContent-Length: 3\r\n\r\nnew",
+        );
+        assert!(matches!(response.status_code, HttpStatusCode::NoContent));
+        assert_eq!(std::fs::read(base.join("a.txt")).unwrap(), b"new");
+
+        let response = file_request(&base, "PUT /files/../a.txt HTTP/1.1\r\n\r\n");
+        assert!(matches!(response.status_code, HttpStatusCode::Forbidden));
+    }
+
+    #[test]
+    fn test_file_request_post_does_not_replace() {
+        let base = TempDir::new("post-existing");
+        std::fs::write(base.join("a.txt"), "original").unwrap();
+
+        let response = file_request(
+            &base,
+            "POST /files/a.txt HTTP/1.1\r\nContent-Length: 3\r\n\r\nnew",
+        );
+        assert!(matches!(response.status_code, HttpStatusCode::Conflict));
+        assert_eq!(std::fs::read(base.join("a.txt")).unwrap(), b"original");
+    }
+
+    #[test]
+    fn test_file_request_multipart_upload() {
+        let base = TempDir::new("multipart");
+        let body = "--b\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nignored\r\n--b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\nuploaded\r\n--b--\r\n";
+        let raw_request = format!(
+            "POST /files/a.txt HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+
+        let response = file_request(&base, &raw_request);
+        assert!(matches!(response.status_code, HttpStatusCode::Created));
+        assert_eq!(std::fs::read(base.join("a.txt")).unwrap(), b"uploaded");
+
+        let body =
+            "--b\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nno file\r\n--b--\r\n";
+        let raw_request = format!(
+            "POST /files/b.txt HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let response = file_request(&base, &raw_request);
+        assert!(matches!(response.status_code, HttpStatusCode::BadRequest));
+        assert!(!base.join("b.txt").exists());
+    }
+
+    #[test]
+    fn test_echo_multibyte_content_length() {
+        let router = build_router(&quiet(), &Arc::default());
+        let message = "h\u{e9}llo";
+        assert_eq!(message.chars().count(), 5);
+        assert_eq!(message.len(), 6);
+
+        for raw_request in [
+            "GET /echo/h\u{e9}llo HTTP/1.1\r\n\r\n",
+            "GET /echo/h%C3%A9llo HTTP/1.1\r\n\r\n",
+        ] {
+            let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+            let bytes = router.route(&request).unwrap().to_bytes();
+
+            let response = String::from_utf8(bytes).unwrap();
+            assert!(response.contains("\r\nContent-Length: 6\r\n"));
+            assert!(response.ends_with(&format!("\r\n\r\n{}", message)));
+        }
+    }
+
+    #[test]
+    fn test_echo_body() {
+        let router = build_router(&quiet(), &Arc::default());
+        let body = r#"{"name":"test"}"#;
+        let raw_request = format!(
+            "POST /echo HTTP/1.1\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+
+        let response = router.route(&request).unwrap();
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+        assert_eq!(
+            response.header("Content-Type"),
+            Some("application/json; charset=utf-8")
+        );
+        let content = response.content.unwrap();
+        assert_eq!(content.content_type, http::HttpContentType::ApplicationJson);
+        assert_eq!(content.content.as_bytes().unwrap(), body.as_bytes());
+
+        let request = HttpRequest::new(b"POST /echo HTTP/1.1\r\n\r\n").unwrap();
+        let response = router.route(&request).unwrap();
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+        assert!(response.content.is_none());
+    }
+
+    #[test]
+    fn test_health_check() {
+        let request = HttpRequest::new(b"GET /healthz HTTP/1.1\r\n\r\n").unwrap();
+
+        let response = build_router(&quiet(), &Arc::default())
+            .route(&request)
+            .unwrap();
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+        let content = response.content.unwrap();
+        assert_eq!(content.content_type, http::HttpContentType::TextPlain);
+        assert_eq!(content.content.as_bytes().unwrap(), b"ok");
+
+        let config = Config {
+            health_check: false,
+            ..quiet()
+        };
+        let response = build_router(&config, &Arc::default())
+            .route(&request)
+            .unwrap();
+        assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+    }
+
+    #[test]
+    fn test_document_root() {
+        let base = TempDir::new("root");
+        std::fs::write(base.join("style.css"), "body {}").unwrap();
+        std::fs::write(base.join("index.html"), "<h1>home</h1>").unwrap();
+        std::fs::create_dir_all(base.join("echo")).unwrap();
+        std::fs::write(base.join("echo/abc"), "shadowed").unwrap();
+        let router = build_router(
+            &Config {
+                root: Some(base.to_path_buf()),
+                ..quiet()
+            },
+            &Arc::default(),
+        );
+        let route = |raw_request: &str| {
+            let request = HttpRequest::new(raw_request.as_bytes()).unwrap();
+            router.route(&request).unwrap()
+        };
+
+        let response = route("GET /style.css HTTP/1.1\r\n\r\n");
+        assert!(matches!(response.status_code, HttpStatusCode::Ok));
+        let content = response.content.unwrap();
+        assert_eq!(content.content_type, http::HttpContentType::TextCss);
+        assert_eq!(content.content.as_bytes().unwrap(), b"body {}");
+
+        let response = route("GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(
+            response.content.unwrap().content.as_bytes().unwrap(),
+            b"<h1>home</h1>"
+        );
+
+        let response = route("GET /missing.css HTTP/1.1\r\n\r\n");
+        assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+
+        // Built-in routes win over files at the same path.
+        let response = route("GET /echo/abc HTTP/1.1\r\n\r\n");
+        assert_eq!(
+            response.content.unwrap().content.as_bytes().unwrap(),
+            b"abc"
+        );
+    }
+
+    #[test]
+    fn test_file_request_delete() {
+        let base = TempDir::new("delete");
+        std::fs::write(base.join("a.txt"), "a").unwrap();
+
+        let response = file_request(&base, "DELETE /files/a.txt HTTP/1.1\r\n\r\n");
+        assert!(matches!(response.status_code, HttpStatusCode::NoContent));
+        assert!(!base.join("a.txt").exists());
+
+        let response = file_request(&base, "DELETE /files/a.txt HTTP/1.1\r\n\r\n");
+        assert!(matches!(response.status_code, HttpStatusCode::NotFound));
+
+        let response = file_request(&base, "DELETE /files/../a.txt HTTP/1.1\r\n\r\n");
+        assert!(matches!(response.status_code, HttpStatusCode::Forbidden));
+    }
+}
@@ -0,0 +1,1396 @@
+use crate::config::{Config, LogFormat};
+use crate::date::format_http_date;
+use crate::http::{
+    chunked_length, content_length, find_header_end, is_chunked, parse_header_block, HttpHeaders,
+    HttpMethod, HttpRequest, HttpResponse, HttpStatusCode, HttpVersion, ParseError,
+};
+use crate::json::JsonValue;
+use crate::metrics::Metrics;
+use crate::pool::ThreadPool;
+use crate::router::Router;
+use crate::routes::HEALTH_CHECK_PATH;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// A listening socket together with the settings and routes its connections are
+/// served with.
+pub struct Server {
+    listener: TcpListener,
+    config: Config,
+    router: Router,
+    metrics: Arc<Metrics>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl Server {
+    /// Listens on `addr`, with the default config and no routes until they're set.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Server> {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            config: Config::default(),
+            router: Router::new(),
+            metrics: Arc::default(),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    pub fn with_config(mut self, config: Config) -> Server {
+        self.config = config;
+        self
+    }
+
+    pub fn with_router(mut self, router: Router) -> Server {
+        self.router = router;
+        self
+    }
+
+    /// The counters the server updates as it answers requests, for a route to report.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// The address actually listened on, which has the assigned port when bound to
+    /// port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Makes Ctrl-C stop [`Server::run`] once the connections in progress are done.
+    pub fn shutdown_on_ctrl_c(&self) {
+        let addr = self.local_addr().expect("failed to get listener address");
+        spawn_shutdown_handler(Arc::clone(&self.shutdown), addr);
+    }
+
+    /// Accepts connections and serves them on a pool of `config.threads` workers until
+    /// the server is shut down.
+    pub fn run(self) {
+        let pool = ThreadPool::new(self.config.threads, self.config.threads);
+        let router = Arc::new(self.router);
+        let config = Arc::new(self.config);
+        if !config.stats_interval.is_zero() {
+            spawn_stats_logger(
+                Arc::clone(&self.metrics),
+                config.stats_interval,
+                Arc::clone(&self.shutdown),
+            );
+        }
+
+        for stream in self.listener.incoming() {
+            if self.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+
+            match stream {
+                Ok(stream) => {
+                    let router = Arc::clone(&router);
+                    let config = Arc::clone(&config);
+                    let metrics = Arc::clone(&self.metrics);
+                    let shutdown = Arc::clone(&self.shutdown);
+                    pool.execute(move || {
+                        handle_connection(stream, &router, &config, &metrics, &shutdown);
+                    });
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                }
+            }
+        }
+
+        // Dropping the pool waits for the connections already being served to finish.
+        drop(pool);
+    }
+}
+
+/// Logs a summary of `metrics` every `interval` on a background thread, until the
+/// server shuts down.
+fn spawn_stats_logger(metrics: Arc<Metrics>, interval: Duration, shutdown: Arc<AtomicBool>) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        eprintln!("stats: {}", metrics.summary());
+    });
+}
+
+/// Waits for Ctrl-C on a background thread, then flags `shutdown` and connects to the
+/// listener at `addr` so the blocked accept loop wakes up and sees the flag.
+fn spawn_shutdown_handler(shutdown: Arc<AtomicBool>, mut addr: SocketAddr) {
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr {
+            SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .expect("failed to build signal runtime");
+        if let Err(e) = runtime.block_on(tokio::signal::ctrl_c()) {
+            eprintln!("error: failed to listen for Ctrl-C: {}", e);
+            return;
+        }
+
+        eprintln!("shutting down");
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = TcpStream::connect(addr);
+    });
+}
+
+/// How much of a request [`read_request`] will take before giving up on it.
+#[derive(Clone, Copy, Debug)]
+struct ReadLimits {
+    request_line: usize,
+    headers: usize,
+    body: usize,
+    /// How long the header block may take to arrive, from its first byte.
+    header_timeout: Duration,
+}
+
+impl From<&Config> for ReadLimits {
+    fn from(config: &Config) -> Self {
+        ReadLimits {
+            request_line: config.max_request_line,
+            headers: config.max_header_size,
+            body: config.max_body_size,
+            header_timeout: config.header_timeout,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ReadError {
+    /// The request line or the header block is over its limit.
+    HeadersTooLarge,
+    /// The body is over its limit.
+    TooLarge,
+    /// The headers don't say how long the body is in a way that can be trusted, like
+    /// an invalid or conflicting `Content-Length`.
+    Malformed(ParseError),
+    /// The client stopped sending partway through the request headers, or took longer
+    /// than the header timeout to send them all.
+    TimedOut,
+    Io(io::Error),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+/// Bodies smaller than this are sent uncompressed even when the client accepts
+/// compression.
+const MIN_COMPRESS_SIZE: usize = 256;
+
+/// How long an idle keep-alive connection is held open waiting for the next request.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves requests on `stream` until the client asks to close the connection, stops
+/// sending, sits idle for longer than [`KEEP_ALIVE_TIMEOUT`], or the server is shutting
+/// down.
+fn handle_connection(
+    mut stream: TcpStream,
+    router: &Router,
+    config: &Config,
+    metrics: &Metrics,
+    shutdown: &AtomicBool,
+) {
+    let _open = metrics.open_connection();
+    if let Err(e) = stream.set_write_timeout(Some(config.timeout)) {
+        eprintln!("error: failed to set write timeout: {}", e);
+        return;
+    }
+
+    // Bytes the client sent past the end of the last request: the start of the next
+    // one, if it didn't wait for the response before sending it.
+    let mut buffered = Vec::new();
+    let remote_addr = stream.peer_addr().ok();
+    while (!buffered.is_empty() || wait_for_request(&stream, config))
+        && handle_request(
+            &mut stream,
+            &mut buffered,
+            remote_addr,
+            router,
+            config,
+            metrics,
+        )
+        && !shutdown.load(Ordering::SeqCst)
+    {}
+}
+
+/// Waits up to [`KEEP_ALIVE_TIMEOUT`] for the next request to start arriving, then
+/// allows the configured timeout for each read of the rest of it. Returns whether
+/// there's a request to handle.
+fn wait_for_request(stream: &TcpStream, config: &Config) -> bool {
+    let waited = stream
+        .set_read_timeout(Some(KEEP_ALIVE_TIMEOUT))
+        .and_then(|()| stream.peek(&mut [0]))
+        .and_then(|n| stream.set_read_timeout(Some(config.timeout)).map(|()| n));
+
+    match waited {
+        Ok(n) => n > 0,
+        Err(e) => {
+            if !is_timeout(&e) {
+                eprintln!("error: failed to read stream: {}", e);
+            }
+            false
+        }
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+/// Reads and answers a single request, starting with the `buffered` bytes already read
+/// from `stream`, returning whether the connection should be kept open for another one.
+/// `remote_addr` is the client's address, if it's known, and is handed on to the
+/// router with the request.
+fn handle_request(
+    stream: &mut (impl Read + Write),
+    buffered: &mut Vec<u8>,
+    remote_addr: Option<SocketAddr>,
+    router: &Router,
+    config: &Config,
+    metrics: &Metrics,
+) -> bool {
+    let read = read_request(stream, buffered, ReadLimits::from(config));
+    let mut log = RequestLog {
+        line: String::from("-"),
+        method: None,
+        path: None,
+        remote_addr,
+        id: next_request_id(),
+        start: Instant::now(),
+        quiet: false,
+    };
+
+    let buf = match read {
+        // The client closed the connection without sending anything, so there is
+        // nothing to answer.
+        Ok(buf) if buf.is_empty() => return false,
+        Ok(buf) => buf,
+        Err(ReadError::HeadersTooLarge) => {
+            let response = HttpResponse::new(HttpStatusCode::RequestHeaderFieldsTooLarge, None);
+            send_response(stream, config, metrics, &log, response);
+            return false;
+        }
+        Err(ReadError::TooLarge) => {
+            let response = HttpResponse::new(HttpStatusCode::PayloadTooLarge, None);
+            send_response(stream, config, metrics, &log, response);
+            return false;
+        }
+        Err(ReadError::TimedOut) => {
+            let response = HttpResponse::new(HttpStatusCode::RequestTimeout, None);
+            send_response(stream, config, metrics, &log, response);
+            return false;
+        }
+        // Where this request ends, and so where the next one starts, isn't known.
+        Err(ReadError::Malformed(e)) => {
+            eprintln!("error: {}", e);
+            let response = HttpResponse::new(HttpStatusCode::BadRequest, None);
+            send_response(stream, config, metrics, &log, response);
+            return false;
+        }
+        Err(ReadError::Io(e)) => {
+            if !is_timeout(&e) {
+                eprintln!("error: failed to read stream: {}", e);
+            }
+            return false;
+        }
+    };
+
+    let parsed = if config.strict_headers {
+        HttpRequest::new_strict(&buf)
+    } else {
+        HttpRequest::new(&buf)
+    };
+    let parsed = parsed.and_then(|request| {
+        // Every HTTP/1.1 request has to name the host it's for.
+        if request.version == HttpVersion::Http11 && request.header("Host").is_none() {
+            Err(ParseError::MissingHost)
+        } else {
+            Ok(request)
+        }
+    });
+    let (mut response, keep_alive) = match parsed {
+        Ok(mut request) => {
+            request.remote_addr = remote_addr;
+            log.line = format!("{} {} {}", request.method, request.path, request.version);
+            log.method = Some(request.method.to_string());
+            log.path = Some(request.path.clone());
+            // Health checks come every few seconds and would drown out everything else.
+            log.quiet = config.health_check && request.path == HEALTH_CHECK_PATH;
+            if let Some(id) = request
+                .header("X-Request-Id")
+                .filter(|id| valid_request_id(id))
+            {
+                log.id = id.to_string();
+            }
+            let keep_alive = request.is_keep_alive();
+            let head = request.method == HttpMethod::Head;
+            // Repeated Accept-Encoding lines mean the same as one comma-separated list.
+            let accept_encoding = request.header_all("Accept-Encoding").join(", ");
+            let mut response = match config.maintenance {
+                // The load balancer still needs to see the server is up.
+                Some(retry_after) if request.path != HEALTH_CHECK_PATH => {
+                    HttpResponse::service_unavailable(retry_after)
+                }
+                _ => router.route(&request).unwrap_or_else(|e| {
+                    eprintln!("error: {:#}", e);
+                    HttpResponse::new(HttpStatusCode::InternalServerError, None)
+                }),
+            };
+            if let Some(cors) = &config.cors {
+                response = cors.apply(&request, response);
+            }
+            response = response.compress(Some(&accept_encoding), MIN_COMPRESS_SIZE);
+            response.head = head;
+            (response, keep_alive)
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            let status_code = match e {
+                ParseError::UnsupportedVersion(_) => HttpStatusCode::HttpVersionNotSupported,
+                // A method the server doesn't know isn't allowed anywhere, unlike one
+                // that just has no route for the path, answered 405 by the router.
+                ParseError::UnknownMethod(_) => HttpStatusCode::NotImplemented,
+                _ => HttpStatusCode::BadRequest,
+            };
+            let response = HttpResponse::new(status_code, None);
+            (response, false)
+        }
+    };
+
+    response.keep_alive = keep_alive;
+    let sent = send_response(stream, config, metrics, &log, response);
+
+    sent && keep_alive
+}
+
+/// What the access log records about a request besides its response.
+struct RequestLog {
+    /// The request line, or `-` when there wasn't a valid one.
+    line: String,
+    /// The method and path, when there was a valid request line.
+    method: Option<String>,
+    path: Option<String>,
+    remote_addr: Option<SocketAddr>,
+    /// Sent back as `X-Request-Id` so the response can be matched with the log line.
+    id: String,
+    start: Instant,
+    /// Whether to leave the request out of the log even when logging is on.
+    quiet: bool,
+}
+
+/// Hands out request IDs unique to this process: its PID and a counter, in hex.
+fn next_request_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{:x}", std::process::id(), n)
+}
+
+/// Whether a client-supplied `X-Request-Id` is short and plain enough to reuse, rather
+/// than echoing arbitrary input into the logs.
+fn valid_request_id(id: &str) -> bool {
+    (1..=128).contains(&id.len()) && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Writes `response` to `stream` with the configured `Server` header and the request's
+/// ID, counts it in `metrics` and, unless logging is off, logs it against the request
+/// it answers. Returns whether all of it was written; if not, the connection is no
+/// use for another response.
+fn send_response(
+    stream: &mut impl Write,
+    config: &Config,
+    metrics: &Metrics,
+    log: &RequestLog,
+    response: HttpResponse,
+) -> bool {
+    let mut response = config
+        .error_pages
+        .apply(response)
+        .with_header("X-Request-Id", &log.id);
+    response.server = config.server.clone();
+    let status = response.status_code.code();
+    let bytes = match response.write_to(stream) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("error: failed to write response: {}", e);
+            return false;
+        }
+    };
+    metrics.record(status, bytes);
+
+    if config.log && !log.quiet {
+        let (date, elapsed) = (SystemTime::now(), log.start.elapsed());
+        let line = match config.log_format {
+            LogFormat::Common => access_log_line(date, &log.line, &log.id, status, bytes, elapsed),
+            LogFormat::Json => access_log_json(date, log, status, bytes, elapsed),
+        };
+        eprintln!("{}", line);
+    }
+    true
+}
+
+/// Formats a request in the spirit of the Common Log Format:
+/// `[date] "request line" status bytes duration id`.
+fn access_log_line(
+    date: SystemTime,
+    request_line: &str,
+    request_id: &str,
+    status: u16,
+    bytes: u64,
+    elapsed: Duration,
+) -> String {
+    format!(
+        "[{}] \"{}\" {} {} {:.3}ms {}",
+        format_http_date(date),
+        request_line,
+        status,
+        bytes,
+        elapsed.as_secs_f64() * 1000.0,
+        request_id
+    )
+}
+
+/// Formats a request as a JSON object on one line, for log aggregators. Fields that
+/// aren't known, like the method of a request that couldn't be parsed, are `null`.
+fn access_log_json(
+    date: SystemTime,
+    log: &RequestLog,
+    status: u16,
+    bytes: u64,
+    elapsed: Duration,
+) -> String {
+    let string = |value: Option<String>| value.map_or(JsonValue::Null, JsonValue::String);
+    JsonValue::Object(vec![
+        (
+            String::from("time"),
+            JsonValue::String(format_http_date(date)),
+        ),
+        (String::from("method"), string(log.method.clone())),
+        (String::from("path"), string(log.path.clone())),
+        (String::from("status"), JsonValue::Number(status.into())),
+        (String::from("bytes"), JsonValue::Number(bytes as f64)),
+        (
+            String::from("duration_ms"),
+            JsonValue::Number(elapsed.as_secs_f64() * 1000.0),
+        ),
+        (
+            String::from("remote_addr"),
+            string(log.remote_addr.map(|addr| addr.to_string())),
+        ),
+        (
+            String::from("request_id"),
+            JsonValue::String(log.id.clone()),
+        ),
+    ])
+    .to_string()
+}
+
+/// Reads a full request from `stream`: everything up to the blank line ending the
+/// headers, followed by exactly `Content-Length` bytes of body, or by every chunk of a
+/// chunked one. Reading stops as soon as the request line, headers or body are known
+/// to exceed their `limits`, or once the headers have taken longer than its header
+/// timeout to arrive, so a client can't hold the connection by trickling them in.
+/// An invalid or conflicting `Content-Length` is an error too, since there's no telling
+/// where the body ends.
+/// A client that sent `Expect: 100-continue` and is waiting for the go-ahead gets an
+/// interim `100 Continue` before the body is read, unless it's already too large.
+///
+/// The request starts with the `buffered` bytes left over from the last one, and
+/// anything read past its end is left there for the next.
+fn read_request(
+    stream: &mut (impl Read + Write),
+    buffered: &mut Vec<u8>,
+    limits: ReadLimits,
+) -> Result<Vec<u8>, ReadError> {
+    let mut buf = std::mem::take(buffered);
+    let mut chunk = [0; 1024];
+    let mut started = (!buf.is_empty()).then(Instant::now);
+
+    let header_end = loop {
+        // The request line's limit is without its line ending, the header block's with
+        // every line ending, up to the blank line.
+        let request_line_len = match buf.iter().position(|&byte| byte == b'\n') {
+            Some(end) => buf[..end].strip_suffix(b"\r").unwrap_or(&buf[..end]).len(),
+            None => buf.len(),
+        };
+        if request_line_len > limits.request_line {
+            return Err(ReadError::HeadersTooLarge);
+        }
+        if let Some(pos) = find_header_end(&buf) {
+            if pos > limits.headers {
+                return Err(ReadError::HeadersTooLarge);
+            }
+            break pos;
+        }
+        if buf.len() > limits.headers {
+            return Err(ReadError::HeadersTooLarge);
+        }
+        if started.is_some_and(|started| started.elapsed() > limits.header_timeout) {
+            return Err(ReadError::TimedOut);
+        }
+
+        let n = match stream.read(&mut chunk) {
+            Ok(n) => n,
+            Err(e) if is_timeout(&e) && !buf.is_empty() => return Err(ReadError::TimedOut),
+            Err(e) => return Err(e.into()),
+        };
+        if n == 0 {
+            return Ok(buf);
+        }
+        started.get_or_insert_with(Instant::now);
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    // Framed by the same header parse the request gets, so the two can't disagree on
+    // where the body ends. Malformed lines are left for the parser to reject.
+    let headers = parse_header_block(&buf[..header_end], false).map_err(ReadError::Malformed)?;
+
+    if headers.get("Transfer-Encoding").is_some_and(is_chunked) {
+        send_continue(stream, &headers, &buf, header_end)?;
+        return read_chunked_body(stream, buf, buffered, header_end, limits.body);
+    }
+
+    let content_length = content_length(&headers)
+        .map_err(ReadError::Malformed)?
+        .unwrap_or(0);
+    if content_length > limits.body {
+        return Err(ReadError::TooLarge);
+    }
+    if content_length > 0 {
+        send_continue(stream, &headers, &buf, header_end)?;
+    }
+    let total = header_end + content_length;
+
+    while buf.len() < total {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    if buf.len() > total {
+        *buffered = buf.split_off(total);
+    }
+
+    Ok(buf)
+}
+
+/// Keeps reading until the final chunk after `header_end` has arrived. A malformed
+/// chunk stops the read early and is left for the parser to reject, while a body
+/// whose encoding grows past `max_body_size` is rejected here. Anything after the
+/// final chunk is left in `buffered`.
+fn read_chunked_body(
+    stream: &mut impl Read,
+    mut buf: Vec<u8>,
+    buffered: &mut Vec<u8>,
+    header_end: usize,
+    max_body_size: usize,
+) -> Result<Vec<u8>, ReadError> {
+    let mut chunk = [0; 1024];
+
+    loop {
+        match chunked_length(&buf[header_end..]) {
+            Ok(Some(len)) => {
+                *buffered = buf.split_off(header_end + len);
+                return Ok(buf);
+            }
+            Ok(None) => {}
+            Err(_) => return Ok(buf),
+        }
+        if buf.len() - header_end > max_body_size {
+            return Err(ReadError::TooLarge);
+        }
+
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Ok(buf);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// Writes `100 Continue` if the request asked for it and none of its body has been
+/// sent yet.
+fn send_continue(
+    stream: &mut impl Write,
+    headers: &HttpHeaders,
+    buf: &[u8],
+    header_end: usize,
+) -> io::Result<()> {
+    let expects_continue = headers
+        .get("Expect")
+        .is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"));
+
+    if expects_continue && buf.len() == header_end {
+        write!(stream, "HTTP/1.1 {}\r\n\r\n", HttpStatusCode::Continue)?;
+        stream.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cors::Cors;
+    use crate::http::{BodySource, HttpContent, HttpContentType};
+    use crate::json::parse_json;
+    use crate::routes::build_router;
+    use crate::test_support::{quiet, serving, TempDir};
+
+    /// A stream that hands out its data in fixed segments, like a client sending a
+    /// request across several TCP packets, and records everything written back.
+    struct MockStream {
+        segments: Vec<Vec<u8>>,
+        written: Vec<u8>,
+        /// Whether reads past the last segment time out rather than hit end of stream.
+        stalls: bool,
+        /// How many bytes had been written when each read was made.
+        written_at_read: Vec<usize>,
+        /// The most bytes a single write accepts, or `None` for a write that fails.
+        max_write: Option<usize>,
+        /// How long each read takes.
+        read_delay: Duration,
+    }
+
+    impl MockStream {
+        fn new(segments: &[&[u8]]) -> MockStream {
+            MockStream {
+                segments: segments.iter().rev().map(|s| s.to_vec()).collect(),
+                written: Vec::new(),
+                stalls: false,
+                written_at_read: Vec::new(),
+                max_write: Some(usize::MAX),
+                read_delay: Duration::ZERO,
+            }
+        }
+
+        fn stalling(segments: &[&[u8]]) -> MockStream {
+            MockStream {
+                stalls: true,
+                ..MockStream::new(segments)
+            }
+        }
+
+        /// Delivers `data` a byte at a time, each read taking `read_delay`.
+        fn trickling(data: &[u8], read_delay: Duration) -> MockStream {
+            let bytes: Vec<&[u8]> = data.chunks(1).collect();
+            MockStream {
+                read_delay,
+                ..MockStream::new(&bytes)
+            }
+        }
+
+        fn written(&self) -> String {
+            String::from_utf8_lossy(&self.written).into_owned()
+        }
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.written_at_read.push(self.written.len());
+            thread::sleep(self.read_delay);
+            match self.segments.pop() {
+                Some(mut segment) => {
+                    let n = segment.len().min(buf.len());
+                    buf[..n].copy_from_slice(&segment[..n]);
+                    if n < segment.len() {
+                        self.segments.push(segment.split_off(n));
+                    }
+                    Ok(n)
+                }
+                None if self.stalls => Err(io::Error::from(ErrorKind::WouldBlock)),
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let max_write = self
+                .max_write
+                .ok_or_else(|| io::Error::from(ErrorKind::BrokenPipe))?;
+            let n = buf.len().min(max_write);
+            self.written.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_read_request_larger_than_one_read() {
+        let path = "a".repeat(4000);
+        let raw_request = format!("GET /{} HTTP/1.1\r\n\r\n", path);
+        let mut stream = MockStream::new(&[raw_request.as_bytes()]);
+
+        let buf = read_request(&mut stream, &mut Vec::new(), limits()).unwrap();
+        assert_eq!(buf, raw_request.as_bytes());
+    }
+
+    #[test]
+    fn test_read_request_body_split_across_segments() {
+        let mut stream = MockStream::new(&[
+            b"POST /files/a HTTP/1.1\r\nContent-Length: 10\r",
+            b"\n\r\nhello",
+            b" wor",
+            b"ld",
+        ]);
+
+        let buf = read_request(&mut stream, &mut Vec::new(), limits()).unwrap();
+        assert_eq!(
+            buf,
+            b"POST /files/a HTTP/1.1\r\nContent-Length: 10\r\n\r\nhello worl"
+        );
+    }
+
+    #[test]
+    fn test_read_chunked_request() {
+        let mut stream = MockStream::new(&[
+            b"POST /files/a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel",
+            b"lo\r\n6\r\n world\r\n",
+            b"0\r\n\r\nGET / HTTP/1.1\r\n\r\n",
+        ]);
+
+        let mut buffered = Vec::new();
+        let buf = read_request(&mut stream, &mut buffered, limits()).unwrap();
+        let request = HttpRequest::new(&buf).unwrap();
+        assert_eq!(request.body_bytes(), b"hello world");
+        assert_eq!(buffered, b"GET / HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_read_pipelined_requests() {
+        let mut stream = MockStream::new(&[
+            b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET /a HTTP/1.1\r\n",
+            b"\r\nGET /b HTTP/1.1\r\n\r\n",
+        ]);
+        let mut buffered = Vec::new();
+
+        let buf = read_request(&mut stream, &mut buffered, limits()).unwrap();
+        assert_eq!(
+            buf,
+            b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello"
+        );
+        assert_eq!(buffered, b"GET /a HTTP/1.1\r\n");
+
+        let buf = read_request(&mut stream, &mut buffered, limits()).unwrap();
+        assert_eq!(buf, b"GET /a HTTP/1.1\r\n\r\n");
+        assert_eq!(buffered, b"GET /b HTTP/1.1\r\n\r\n");
+
+        // The last request was read in full with the one before it.
+        let buf = read_request(&mut stream, &mut buffered, limits()).unwrap();
+        assert_eq!(buf, b"GET /b HTTP/1.1\r\n\r\n");
+        assert!(buffered.is_empty());
+    }
+
+    #[test]
+    fn test_expect_continue() {
+        const CONTINUE: &str = "HTTP/1.1 100 Continue\r\n\r\n";
+        let mut stream = MockStream::new(&[
+            b"POST /echo/a HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n",
+            b"hello",
+        ]);
+
+        let buf = read_request(&mut stream, &mut Vec::new(), limits()).unwrap();
+        assert!(buf.ends_with(b"\r\n\r\nhello"));
+        assert_eq!(stream.written(), CONTINUE);
+        // The headers were read before anything was written, the body only after.
+        assert_eq!(stream.written_at_read, [0, CONTINUE.len()]);
+
+        // A body sent without waiting, or one that's too large, gets no interim response.
+        let mut stream = MockStream::new(&[
+            b"POST /echo/a HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\nhello",
+        ]);
+        read_request(&mut stream, &mut Vec::new(), limits()).unwrap();
+        assert_eq!(stream.written(), "");
+
+        let mut stream = MockStream::new(&[
+            b"POST /echo/a HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n",
+        ]);
+        assert!(matches!(
+            read_request(
+                &mut stream,
+                &mut Vec::new(),
+                ReadLimits {
+                    body: 4,
+                    ..limits()
+                }
+            ),
+            Err(ReadError::TooLarge)
+        ));
+        assert_eq!(stream.written(), "");
+    }
+
+    #[test]
+    fn test_read_request_headers_too_large() {
+        let limits = ReadLimits {
+            request_line: 20,
+            headers: 100,
+            ..limits()
+        };
+
+        let mut stream = MockStream::new(&[b"GET /a HTTP/1.1\r\n", b"Host: localhost\r\n\r\n"]);
+        assert!(read_request(&mut stream, &mut Vec::new(), limits).is_ok());
+
+        // Rejected before the line has even ended.
+        let mut stream = MockStream::new(&[b"GET /aaaaaaaaaaaaaaaaaaaa"]);
+        assert!(matches!(
+            read_request(&mut stream, &mut Vec::new(), limits),
+            Err(ReadError::HeadersTooLarge)
+        ));
+
+        let header = format!("X-Padding: {}\r\n", "a".repeat(100));
+        let raw_request = format!("GET /a HTTP/1.1\r\n{}\r\n", header);
+        let mut stream = MockStream::new(&[raw_request.as_bytes()]);
+        assert!(matches!(
+            read_request(&mut stream, &mut Vec::new(), limits),
+            Err(ReadError::HeadersTooLarge)
+        ));
+
+        let config = Config {
+            max_header_size: 100,
+            ..quiet()
+        };
+        let (keep_alive, written) = respond(raw_request.as_bytes(), &config);
+        assert!(!keep_alive);
+        assert!(written.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+    }
+
+    #[test]
+    fn test_read_request_rejects_untrustworthy_content_length() {
+        for head in [
+            "Content-Length: abc",
+            "Content-Length: -1",
+            "Content-Length: 5\r\nContent-Length: 6",
+            "Content-Length: 5, 6",
+        ] {
+            let raw_request = format!(
+                "POST /echo HTTP/1.1\r\nHost: localhost\r\n{}\r\n\r\nhello",
+                head
+            );
+            let mut stream = MockStream::new(&[raw_request.as_bytes()]);
+            assert!(
+                matches!(
+                    read_request(&mut stream, &mut Vec::new(), limits()),
+                    Err(ReadError::Malformed(ParseError::InvalidContentLength))
+                ),
+                "{}",
+                head
+            );
+
+            let (keep_alive, written) = respond(raw_request.as_bytes(), &quiet());
+            assert!(!keep_alive);
+            assert!(written.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        }
+
+        // The same length sent twice is unambiguous.
+        let raw_request: &[u8] =
+            b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\nhelloGET";
+        let mut stream = MockStream::new(&[raw_request]);
+        let mut buffered = Vec::new();
+        let buf = read_request(&mut stream, &mut buffered, limits()).unwrap();
+        assert!(buf.ends_with(b"\r\n\r\nhello"));
+        assert_eq!(buffered, b"GET");
+    }
+
+    #[test]
+    fn test_read_request_too_large() {
+        let mut stream =
+            MockStream::new(&[b"POST /files/a HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world"]);
+        assert!(matches!(
+            read_request(
+                &mut stream,
+                &mut Vec::new(),
+                ReadLimits {
+                    body: 10,
+                    ..limits()
+                }
+            ),
+            Err(ReadError::TooLarge)
+        ));
+
+        let mut stream = MockStream::new(&[
+            b"POST /files/a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n",
+            b"6\r\nhello \r\n",
+            b"5\r\nworld\r\n0\r\n\r\n",
+        ]);
+        assert!(matches!(
+            read_request(
+                &mut stream,
+                &mut Vec::new(),
+                ReadLimits {
+                    body: 10,
+                    ..limits()
+                }
+            ),
+            Err(ReadError::TooLarge)
+        ));
+
+        let config = Config {
+            max_body_size: 4,
+            ..quiet()
+        };
+        let (keep_alive, written) = respond(
+            b"POST /files/a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello",
+            &config,
+        );
+        assert!(!keep_alive);
+        assert!(written.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    }
+
+    /// Handles a request on `stream` with the built-in routes for `config`, returning
+    /// whether the connection would be kept open.
+    fn handle(stream: &mut MockStream, config: &Config) -> bool {
+        let router = build_router(config, &Arc::default());
+        handle_request(
+            stream,
+            &mut Vec::new(),
+            None,
+            &router,
+            config,
+            &Metrics::default(),
+        )
+    }
+
+    /// Handles `raw_request` like [`handle`], also returning everything written back.
+    fn respond(raw_request: &[u8], config: &Config) -> (bool, String) {
+        let mut stream = MockStream::new(&[raw_request]);
+        let keep_alive = handle(&mut stream, config);
+        (keep_alive, stream.written())
+    }
+
+    fn limits() -> ReadLimits {
+        ReadLimits::from(&quiet())
+    }
+
+    #[test]
+    fn test_access_log_line() {
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+        let line = access_log_line(
+            date,
+            "GET /echo/abc HTTP/1.1",
+            "1f-2a",
+            200,
+            3,
+            Duration::from_micros(1500),
+        );
+        assert_eq!(
+            line,
+            "[Sun, 06 Nov 1994 08:49:37 GMT] \"GET /echo/abc HTTP/1.1\" 200 3 1.500ms 1f-2a"
+        );
+    }
+
+    #[test]
+    fn test_access_log_json() {
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+        let log = RequestLog {
+            line: String::from("GET /echo/abc HTTP/1.1"),
+            method: Some(String::from("GET")),
+            path: Some(String::from("/echo/abc")),
+            remote_addr: Some(SocketAddr::from((Ipv4Addr::LOCALHOST, 50123))),
+            id: String::from("1f-2a"),
+            start: Instant::now(),
+            quiet: false,
+        };
+        let line = access_log_json(date, &log, 200, 3, Duration::from_micros(1500));
+        assert!(!line.contains('\n'));
+
+        let json = parse_json(&line).unwrap();
+        assert_eq!(
+            json.get("time").and_then(JsonValue::as_str),
+            Some("Sun, 06 Nov 1994 08:49:37 GMT")
+        );
+        assert_eq!(json.get("method").and_then(JsonValue::as_str), Some("GET"));
+        assert_eq!(
+            json.get("path").and_then(JsonValue::as_str),
+            Some("/echo/abc")
+        );
+        assert_eq!(json.get("status").and_then(JsonValue::as_f64), Some(200.0));
+        assert_eq!(json.get("bytes").and_then(JsonValue::as_f64), Some(3.0));
+        assert_eq!(
+            json.get("duration_ms").and_then(JsonValue::as_f64),
+            Some(1.5)
+        );
+        assert_eq!(
+            json.get("remote_addr").and_then(JsonValue::as_str),
+            Some("127.0.0.1:50123")
+        );
+        assert_eq!(
+            json.get("request_id").and_then(JsonValue::as_str),
+            Some("1f-2a")
+        );
+
+        let log = RequestLog {
+            method: None,
+            path: None,
+            remote_addr: None,
+            ..log
+        };
+        let json = parse_json(&access_log_json(date, &log, 400, 0, Duration::ZERO)).unwrap();
+        assert_eq!(json.get("method"), Some(&JsonValue::Null));
+        assert_eq!(json.get("remote_addr"), Some(&JsonValue::Null));
+    }
+
+    #[test]
+    fn test_handle_request_keeps_connection_alive() {
+        let mut stream = MockStream::new(&[b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"]);
+
+        assert!(handle(&mut stream, &quiet()));
+        let written = stream.written();
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(written.contains("\r\nConnection: keep-alive\r\n"));
+        assert!(!handle(&mut stream, &quiet()));
+    }
+
+    #[test]
+    fn test_handle_request_rejects_bad_versions() {
+        let (keep_alive, written) = respond(b"GET / HTTP/3.0\r\n\r\n", &quiet());
+        assert!(!keep_alive);
+        assert!(written.starts_with("HTTP/1.1 505 HTTP Version Not Supported\r\n"));
+
+        let (keep_alive, written) = respond(b"GET /\r\n\r\n", &quiet());
+        assert!(!keep_alive);
+        assert!(written.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[test]
+    fn test_partial_writes() {
+        let message = "a".repeat(5000);
+        let raw_request = format!("GET /echo/{} HTTP/1.1\r\nHost: localhost\r\n\r\n", message);
+        let mut stream = MockStream {
+            max_write: Some(3),
+            ..MockStream::new(&[raw_request.as_bytes()])
+        };
+
+        assert!(handle(&mut stream, &quiet()));
+        let written = stream.written();
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(written.ends_with(&format!("\r\n\r\n{}", message)));
+
+        // A client that goes away mid-response just ends the connection.
+        let mut stream = MockStream {
+            max_write: None,
+            ..MockStream::new(&[b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"])
+        };
+        assert!(!handle(&mut stream, &quiet()));
+    }
+
+    #[test]
+    fn test_handle_request_strict_headers() {
+        let raw_request: &[u8] = b"GET /echo/abc HTTP/1.1\r\nHost: localhost\r\nbogus\r\n\r\n";
+        let (_, written) = respond(raw_request, &quiet());
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        let config = Config {
+            strict_headers: true,
+            ..quiet()
+        };
+        let (keep_alive, written) = respond(raw_request, &config);
+        assert!(!keep_alive);
+        assert!(written.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[test]
+    fn test_handle_request_unknown_method() {
+        let (keep_alive, written) = respond(
+            b"BREW /echo/abc HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            &quiet(),
+        );
+        assert!(!keep_alive);
+        assert!(written.starts_with("HTTP/1.1 501 Not Implemented\r\n"));
+    }
+
+    #[test]
+    fn test_handle_request_maintenance() {
+        let config = Config {
+            maintenance: Some(Duration::from_secs(60)),
+            ..quiet()
+        };
+        let get = |path: &str| {
+            let raw_request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            respond(raw_request.as_bytes(), &config).1
+        };
+
+        let written = get("/echo/abc");
+        assert!(written.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(written.contains("\r\nRetry-After: 60\r\n"));
+        assert!(get(HEALTH_CHECK_PATH).starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_error_page() {
+        let mut config = quiet();
+        config.error_pages.insert(
+            HttpStatusCode::NotFound,
+            HttpContent {
+                content: b"<p>Nothing here</p>".to_vec(),
+                content_type: HttpContentType::TextHtml,
+            },
+        );
+
+        let (_, written) = respond(b"GET /missing HTTP/1.1\r\nHost: localhost\r\n\r\n", &config);
+        assert!(written.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(written.contains("\r\nContent-Type: text/html\r\n"));
+        assert!(written.ends_with("\r\nContent-Length: 19\r\n\r\n<p>Nothing here</p>"));
+
+        // Responses with a body of their own keep it.
+        let (_, written) = respond(
+            b"GET /echo/abc HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            &config,
+        );
+        assert!(written.ends_with("\r\n\r\nabc"));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Arc::new(Metrics::default());
+        let router = build_router(&quiet(), &metrics);
+        for path in ["/", "/echo/abc", "/missing"] {
+            let raw_request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            let mut stream = MockStream::new(&[raw_request.as_bytes()]);
+            handle_request(
+                &mut stream,
+                &mut Vec::new(),
+                None,
+                &router,
+                &quiet(),
+                &metrics,
+            );
+        }
+        let mut stream = MockStream::new(&[b"GET / HTTP/1.1\r\n\r\n"]);
+        handle_request(
+            &mut stream,
+            &mut Vec::new(),
+            None,
+            &router,
+            &quiet(),
+            &metrics,
+        );
+
+        let mut stream = MockStream::new(&[b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n"]);
+        handle_request(
+            &mut stream,
+            &mut Vec::new(),
+            None,
+            &router,
+            &quiet(),
+            &metrics,
+        );
+        let written = stream.written();
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(written.contains("\nhttp_requests_total 4\n"));
+        assert!(written.contains("\nhttp_responses_total{class=\"2xx\"} 2\n"));
+        assert!(written.contains("\nhttp_responses_total{class=\"4xx\"} 2\n"));
+        // The echoed `abc` and the built-in 404 page.
+        let page = quiet()
+            .error_pages
+            .get(HttpStatusCode::NotFound)
+            .unwrap()
+            .content
+            .len();
+        assert!(written.contains(&format!("\nhttp_response_bytes_total {}\n", 3 + page)));
+        assert_eq!(metrics.requests(), 5);
+    }
+
+    #[test]
+    fn test_request_id() {
+        let request_id = |raw_request: &[u8]| {
+            let (_, written) = respond(raw_request, &quiet());
+            let start = written.find("\r\nX-Request-Id: ").unwrap() + 16;
+            let end = start + written[start..].find("\r\n").unwrap();
+            written[start..end].to_string()
+        };
+
+        let first = request_id(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let second = request_id(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(!first.is_empty());
+        assert_ne!(first, second);
+        // Requests that can't be parsed still get one.
+        assert!(!request_id(b"GET /\r\n\r\n").is_empty());
+
+        assert_eq!(
+            request_id(b"GET / HTTP/1.1\r\nHost: localhost\r\nX-Request-Id: abc-123\r\n\r\n"),
+            "abc-123"
+        );
+        let long = format!(
+            "GET / HTTP/1.1\r\nHost: localhost\r\nX-Request-Id: {}\r\n\r\n",
+            "a".repeat(200)
+        );
+        assert_ne!(request_id(long.as_bytes()), "a".repeat(200));
+    }
+
+    #[test]
+    fn test_handle_request_requires_host() {
+        let (keep_alive, written) = respond(b"GET / HTTP/1.1\r\n\r\n", &quiet());
+        assert!(!keep_alive);
+        assert!(written.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+        let (_, written) = respond(b"GET / HTTP/1.0\r\n\r\n", &quiet());
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_handle_request_ignores_immediate_eof() {
+        let mut stream = MockStream::new(&[]);
+        assert!(!handle(&mut stream, &quiet()));
+        assert!(stream.written().is_empty());
+
+        for raw_request in [&b"   \r\n\r\n"[..], b"\r\n\r\n", b" \t "] {
+            let (keep_alive, written) = respond(raw_request, &quiet());
+            assert!(!keep_alive);
+            assert!(written.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        }
+    }
+
+    #[test]
+    fn test_handle_request_times_out() {
+        let mut stream = MockStream::stalling(&[b"GET / HTTP/1.1\r\nHost: loc"]);
+        assert!(!handle(&mut stream, &quiet()));
+        assert!(stream
+            .written()
+            .starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+
+        let mut stream = MockStream::stalling(&[]);
+        assert!(!handle(&mut stream, &quiet()));
+        assert!(stream.written().is_empty());
+    }
+
+    #[test]
+    fn test_handle_request_times_out_slow_headers() {
+        let config = Config {
+            header_timeout: Duration::from_millis(50),
+            ..quiet()
+        };
+        let raw_request = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        // Every byte arrives well within the read timeout, but the headers as a whole
+        // take several times the header timeout.
+        let mut stream = MockStream::trickling(raw_request, Duration::from_millis(5));
+        assert!(matches!(
+            read_request(&mut stream, &mut Vec::new(), ReadLimits::from(&config)),
+            Err(ReadError::TimedOut)
+        ));
+        assert!(!stream.segments.is_empty());
+
+        let mut stream = MockStream::trickling(raw_request, Duration::from_millis(5));
+        assert!(!handle(&mut stream, &config));
+        assert!(stream
+            .written()
+            .starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+
+        let mut stream = MockStream::trickling(raw_request, Duration::ZERO);
+        let buf = read_request(&mut stream, &mut Vec::new(), ReadLimits::from(&config)).unwrap();
+        assert_eq!(buf, raw_request);
+    }
+
+    #[test]
+    fn test_handle_request_closes_on_request() {
+        let (keep_alive, written) = respond(
+            b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            &quiet(),
+        );
+
+        assert!(!keep_alive);
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(written.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn test_handle_request_http10_closes_without_keep_alive() {
+        let (keep_alive, written) = respond(b"GET / HTTP/1.0\r\n\r\n", &quiet());
+
+        assert!(!keep_alive);
+        assert!(written.contains("\r\nConnection: close\r\n"));
+        assert!(!written.contains("keep-alive"));
+
+        let mut stream = MockStream::new(&[
+            b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n",
+            b"GET / HTTP/1.0\r\nConnection: keep-alive, close\r\n\r\n",
+        ]);
+        assert!(handle(&mut stream, &quiet()));
+        assert!(stream.written().contains("\r\nConnection: keep-alive\r\n"));
+
+        stream.written.clear();
+        assert!(!handle(&mut stream, &quiet()));
+        assert!(stream.written().contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn test_head_request_has_no_body() {
+        let base = TempDir::new("head");
+        std::fs::write(base.join("a.txt"), "hello").unwrap();
+
+        let (_, written) = respond(
+            b"HEAD /files/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            &serving(&base),
+        );
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(written.ends_with("\r\nContent-Length: 5\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_cors_preflight() {
+        let config = Config {
+            cors: Some(Cors::default()),
+            ..quiet()
+        };
+
+        let (_, written) = respond(
+            b"OPTIONS /echo/abc HTTP/1.1\r\nHost: localhost\r\nOrigin: https://example.com\r\nAccess-Control-Request-Method: GET\r\n\r\n",
+            &config,
+        );
+        assert!(written.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(written.contains("\r\nAccess-Control-Allow-Origin: *\r\n"));
+        assert!(written.contains("\r\nAccess-Control-Allow-Methods: GET, HEAD, OPTIONS\r\n"));
+    }
+
+    #[test]
+    fn test_large_file_is_streamed() {
+        let base = TempDir::new("stream");
+        let data: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        std::fs::write(base.join("big.bin"), &data).unwrap();
+
+        let request = HttpRequest::new(b"GET /files/big.bin HTTP/1.1\r\n\r\n").unwrap();
+        let response = build_router(&serving(&base), &Arc::default())
+            .route(&request)
+            .unwrap();
+        assert!(matches!(
+            response.content.unwrap().content,
+            BodySource::File(_, len) if len == data.len() as u64
+        ));
+
+        let mut stream = MockStream::new(&[
+            b"GET /files/big.bin HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\n\r\n",
+        ]);
+        assert!(handle(&mut stream, &serving(&base)));
+        let header_end = find_header_end(&stream.written).unwrap();
+        let head = String::from_utf8_lossy(&stream.written[..header_end]);
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("\r\nContent-Length: {}\r\n", data.len())));
+        assert!(!head.contains("Content-Encoding"));
+        assert!(stream.written[header_end..] == data);
+
+        let mut stream = MockStream::new(&[
+            b"GET /files/big.bin HTTP/1.1\r\nHost: localhost\r\nRange: bytes=1048576-3145727\r\n\r\n",
+        ]);
+        handle(&mut stream, &serving(&base));
+        let header_end = find_header_end(&stream.written).unwrap();
+        assert!(stream
+            .written()
+            .starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(stream.written[header_end..] == data[1024 * 1024..]);
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+/// Fills in the `{{name}}` placeholders in `template` with the HTML-escaped values of
+/// `vars`, so whatever they hold shows up as text rather than markup. Whitespace
+/// inside the braces is ignored, and a placeholder without a value is left as it is.
+pub fn render(template: &str, vars: &HashMap<&str, String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + len + 2;
+        out.push_str(&rest[..start]);
+
+        let name = rest[start + 2..end - 2].trim();
+        match vars.get(name) {
+            Some(value) => out.push_str(&html_escape(value)),
+            None => out.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Escapes the characters that mean something in HTML, so `text` can be put in an
+/// element or a quoted attribute without being read as markup.
+pub fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let vars = HashMap::from([
+            ("title", String::from("Files")),
+            ("count", String::from("3")),
+        ]);
+
+        assert_eq!(
+            render("<h1>{{title}}</h1><p>{{ count }} files</p>", &vars),
+            "<h1>Files</h1><p>3 files</p>"
+        );
+        assert_eq!(render("{{title}}{{title}}", &vars), "FilesFiles");
+        assert_eq!(render("{{missing}} {{title", &vars), "{{missing}} {{title");
+        assert_eq!(render("no placeholders", &vars), "no placeholders");
+    }
+
+    #[test]
+    fn test_html_escape() {
+        assert_eq!(html_escape("<b>&\"</b>"), "&lt;b&gt;&amp;&quot;&lt;/b&gt;");
+        assert_eq!(html_escape("it's"), "it&#39;s");
+        assert_eq!(html_escape("plain text"), "plain text");
+    }
+
+    #[test]
+    fn test_render_escapes_values() {
+        let vars = HashMap::from([("name", String::from("<script>alert(\"x&y\")</script>"))]);
+
+        assert_eq!(
+            render("<p title=\"{{name}}\">{{name}}</p>", &vars),
+            "<p title=\"&lt;script&gt;alert(&quot;x&amp;y&quot;)&lt;/script&gt;\">&lt;script&gt;alert(&quot;x&amp;y&quot;)&lt;/script&gt;</p>"
+        );
+    }
+}
@@ -0,0 +1,52 @@
+//! Fixtures shared by the unit tests.
+
+use crate::config::Config;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// The default config with request logging off, so test output stays readable.
+pub(crate) fn quiet() -> Config {
+    Config {
+        log: false,
+        ..Config::default()
+    }
+}
+
+/// A quiet config serving `directory` under `/files`.
+pub(crate) fn serving(directory: &Path) -> Config {
+    Config {
+        directory: Some(directory.to_path_buf()),
+        ..quiet()
+    }
+}
+
+/// An empty directory of its own for a test, removed with everything in it when
+/// dropped. The name only has to be unique among the tests.
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn new(name: &str) -> TempDir {
+        let path =
+            std::env::temp_dir().join(format!("rust-http-server-{}-{}", name, std::process::id()));
+        // Left over from a run that didn't get to clean up.
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
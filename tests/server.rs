@@ -0,0 +1,209 @@
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::date::parse_http_date;
+use http_server_starter_rust::http::{HttpMethod, HttpResponse, HttpStatusCode};
+use http_server_starter_rust::router::Router;
+use http_server_starter_rust::routes::build_router;
+use http_server_starter_rust::server::Server;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+
+/// Starts a server with the built-in routes on an ephemeral port in the background,
+/// returning its address.
+fn spawn_server() -> SocketAddr {
+    let config = Config {
+        threads: 2,
+        log: false,
+        server: None,
+        ..Config::default()
+    };
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let router = build_router(&config, &server.metrics());
+    let server = server.with_router(router).with_config(config);
+    let addr = server.local_addr().unwrap();
+
+    thread::spawn(move || server.run());
+    addr
+}
+
+/// Sends `raw_request` on a fresh connection and reads the response until the server
+/// closes it, with the `Date` header's value replaced by `<date>` once it's checked.
+fn send(addr: SocketAddr, raw_request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(raw_request.as_bytes()).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let date_start = response.find("\r\nDate: ").unwrap() + "\r\nDate: ".len();
+    let date_end = date_start + response[date_start..].find("\r\n").unwrap();
+    assert!(parse_http_date(&response[date_start..date_end]).is_some());
+    response.replace_range(date_start..date_end, "<date>");
+    response
+}
+
+#[test]
+fn test_echo() {
+    let addr = spawn_server();
+    assert_eq!(
+        send(
+            addr,
+            "GET /echo/hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nX-Request-Id: test\r\n\r\n"
+        ),
+        "HTTP/1.1 200 OK\r\nDate: <date>\r\nConnection: close\r\nX-Request-Id: test\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello"
+    );
+}
+
+#[test]
+fn test_root() {
+    let addr = spawn_server();
+    assert_eq!(
+        send(
+            addr,
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nX-Request-Id: test\r\n\r\n"
+        ),
+        "HTTP/1.1 200 OK\r\nDate: <date>\r\nConnection: close\r\nX-Request-Id: test\r\nContent-Length: 0\r\n\r\n"
+    );
+}
+
+#[test]
+fn test_not_found() {
+    let addr = spawn_server();
+    assert_eq!(
+        send(
+            addr,
+            "GET /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nX-Request-Id: test\r\n\r\n"
+        ),
+        "HTTP/1.1 404 Not Found\r\nDate: <date>\r\nConnection: close\r\nX-Request-Id: test\r\nContent-Type: text/html\r\nContent-Length: 111\r\n\r\n\
+         <!DOCTYPE html>\n<html>\n<head><title>404 Not Found</title></head>\n<body>\n<h1>404 Not Found</h1>\n</body>\n</html>\n"
+    );
+}
+
+#[test]
+fn test_keep_alive_connection() {
+    let addr = spawn_server();
+    let mut stream = TcpStream::connect(addr).unwrap();
+
+    for msg in ["first", "second"] {
+        write!(
+            stream,
+            "GET /echo/{} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            msg
+        )
+        .unwrap();
+
+        // Read one response: the head up to its blank line, then `msg.len()` bytes.
+        let mut head = Vec::new();
+        let mut byte = [0];
+        while !head.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        assert!(head.starts_with(b"HTTP/1.1 200 OK\r\n"));
+        let mut body = vec![0; msg.len()];
+        stream.read_exact(&mut body).unwrap();
+        assert_eq!(body, msg.as_bytes());
+    }
+}
+
+#[test]
+fn test_pipelined_requests() {
+    let addr = spawn_server();
+    let mut stream = TcpStream::connect(addr).unwrap();
+
+    // Both requests go out before either response is read.
+    stream
+        .write_all(
+            b"GET /echo/first HTTP/1.1\r\nHost: localhost\r\nX-Request-Id: test\r\n\r\n\
+              GET /echo/second HTTP/1.1\r\nHost: localhost\r\nX-Request-Id: test\r\nConnection: close\r\n\r\n",
+        )
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let bodies: Vec<&str> = response
+        .split("HTTP/1.1 200 OK\r\n")
+        .skip(1)
+        .map(|response| response.split_once("\r\n\r\n").unwrap().1)
+        .collect();
+    assert_eq!(bodies, ["first", "second"]);
+}
+
+#[test]
+fn test_connection_counters() {
+    let addr = spawn_server();
+
+    // Served, and then held open waiting for another request.
+    let mut idle = TcpStream::connect(addr).unwrap();
+    idle.write_all(b"GET /echo/a HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = [0; 1024];
+    assert!(idle.read(&mut response).unwrap() > 0);
+
+    let metrics = send(
+        addr,
+        "GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+    assert!(metrics.contains("\nhttp_connections_open 2\n"));
+    assert!(metrics.contains("\nhttp_connections_total 2\n"));
+}
+
+#[test]
+fn test_remote_addr() {
+    let mut router = Router::new();
+    router.add(HttpMethod::Get, "/whoami", |request, _| {
+        let remote_addr = request.remote_addr.map(|addr| addr.to_string());
+        Ok(HttpResponse::text(
+            HttpStatusCode::Ok,
+            remote_addr.as_deref().unwrap_or("unknown"),
+        ))
+    });
+    let config = Config {
+        log: false,
+        ..Config::default()
+    };
+    let server = Server::bind("127.0.0.1:0")
+        .unwrap()
+        .with_router(router)
+        .with_config(config);
+    let addr = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let client_addr = stream.local_addr().unwrap();
+    stream
+        .write_all(b"GET /whoami HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(client_addr.ip().is_loopback());
+    assert!(
+        response.ends_with(&format!("\r\n\r\n{}", client_addr)),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn test_rate_limit() {
+    let config = Config {
+        log: false,
+        server: None,
+        rate_limit: Some(2),
+        ..Config::default()
+    };
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let router = build_router(&config, &server.metrics());
+    let server = server.with_router(router).with_config(config);
+    let addr = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+
+    let request = "GET /echo/a HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    for _ in 0..2 {
+        assert!(send(addr, request).starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+    let response = send(addr, request);
+    assert!(response.starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+    assert!(response.contains("\r\nRetry-After: 1\r\n"));
+}